@@ -0,0 +1,295 @@
+// src/action_errors.rs
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use sc2_proto::error::ActionResult;
+use sc2_proto::raw::{ActionRaw_oneof_action, ActionRawUnitCommand, ActionRawUnitCommand_oneof_target};
+use sc2_proto::sc2api::{Action, Request_oneof_request, Response_oneof_response};
+use std::collections::{HashMap, VecDeque};
+use crate::controller::{ProxyActionResultEvent, ProxyGameEndedEvent, ProxyRequestEvent, ProxyResponseEvent};
+use crate::entity_system::EntitySystem;
+use crate::units::{alliance_color, SelectedUnit, UnitAlliance, UnitRegistry};
+
+/// Entries kept in the feed
+const MAX_ENTRIES: usize = 500;
+/// Commands remembered per player to explain observation errors, which only carry unit and ability
+const RECENT_COMMANDS: usize = 200;
+const FLASH_SECS: f32 = 1.5;
+
+/// Where a command was aimed
+#[derive(Debug, Clone, PartialEq)]
+pub enum ActionTarget {
+    None,
+    Point(f32, f32),
+    Unit(u64),
+}
+
+impl ActionTarget {
+    pub fn of(cmd: &ActionRawUnitCommand) -> Self {
+        match cmd.target.as_ref() {
+            Some(ActionRawUnitCommand_oneof_target::target_world_space_pos(p)) => ActionTarget::Point(p.get_x(), p.get_y()),
+            Some(ActionRawUnitCommand_oneof_target::target_unit_tag(tag)) => ActionTarget::Unit(*tag),
+            None => ActionTarget::None,
+        }
+    }
+}
+
+/// The raw command a bot sent, as far as it matters for explaining a failure
+#[derive(Debug, Clone)]
+pub struct ActionCommand {
+    pub ability_id: u32,
+    pub unit_tags: Vec<u64>,
+    pub target: ActionTarget,
+    pub game_loop: u32,
+}
+
+impl ActionCommand {
+    /// Raw unit commands and autocast toggles; other action kinds carry no ability to blame
+    fn from_action(action: &Action, game_loop: u32) -> Option<Self> {
+        match action.get_action_raw().action.as_ref()? {
+            ActionRaw_oneof_action::unit_command(cmd) => Some(Self {
+                ability_id: cmd.get_ability_id() as u32,
+                unit_tags: cmd.get_unit_tags().to_vec(),
+                target: ActionTarget::of(cmd),
+                game_loop,
+            }),
+            ActionRaw_oneof_action::toggle_autocast(toggle) => Some(Self {
+                ability_id: toggle.get_ability_id() as u32,
+                unit_tags: toggle.get_unit_tags().to_vec(),
+                target: ActionTarget::None,
+                game_loop,
+            }),
+            ActionRaw_oneof_action::camera_move(_) => None,
+        }
+    }
+
+    fn describe(&self, entity_system: &EntitySystem) -> String {
+        let ability = entity_system.ability_name(self.ability_id)
+            .map(str::to_string)
+            .unwrap_or_else(|| format!("ability {}", self.ability_id));
+        let target = match self.target {
+            ActionTarget::None => String::new(),
+            ActionTarget::Point(x, y) => format!(" at ({:.1}, {:.1})", x, y),
+            ActionTarget::Unit(tag) => format!(" on {}", tag),
+        };
+        format!("{} by {:?}{}", ability, self.unit_tags, target)
+    }
+}
+
+/// Which message reported the failure
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActionErrorSource {
+    /// `ResponseAction.result`, rejected when the action was sent
+    Response,
+    /// `ResponseObservation.action_errors`, failed while being carried out
+    Observation,
+}
+
+#[derive(Debug, Clone)]
+pub struct ActionErrorEntry {
+    pub player_id: u32,
+    pub game_loop: u32,
+    pub source: ActionErrorSource,
+    pub result: ActionResult,
+    /// The command that failed, when it could be matched
+    pub command: Option<ActionCommand>,
+    /// Units to flash: the command's units, or the unit the observation named
+    pub unit_tags: Vec<u64>,
+}
+
+/// Failed actions of the game in progress, and the recent commands that explain observation errors
+#[derive(Resource, Default)]
+pub struct ActionErrorLog {
+    pub entries: VecDeque<ActionErrorEntry>,
+    recent_commands: HashMap<u32, VecDeque<ActionCommand>>,
+    game_loops: HashMap<u32, u32>,
+    paused: bool,
+    player_filter: Option<u32>,
+}
+
+impl ActionErrorLog {
+    fn push(&mut self, entry: ActionErrorEntry) {
+        self.entries.push_back(entry);
+        if self.entries.len() > MAX_ENTRIES {
+            self.entries.pop_front();
+        }
+    }
+
+    /// The latest command of `player_id` that moved `unit_tag` with `ability_id`, or any ability as a fallback
+    fn find_command(&self, player_id: u32, unit_tag: u64, ability_id: u32) -> Option<ActionCommand> {
+        let commands = self.recent_commands.get(&player_id)?;
+        let of_unit = || commands.iter().rev().filter(|c| c.unit_tags.contains(&unit_tag));
+        of_unit().find(|c| c.ability_id == ability_id).or_else(|| of_unit().next()).cloned()
+    }
+
+    fn reset_pending(&mut self) {
+        self.recent_commands.clear();
+        self.game_loops.clear();
+    }
+}
+
+/// Marks a unit sprite that is flashing because one of its commands failed
+#[derive(Component)]
+pub struct ActionErrorFlash(pub Timer);
+
+/// System to collect rejected actions and observation errors and flash the units involved
+pub fn action_error_system(
+    mut commands: Commands,
+    mut requests: EventReader<ProxyRequestEvent>,
+    mut action_results: EventReader<ProxyActionResultEvent>,
+    mut responses: EventReader<ProxyResponseEvent>,
+    mut game_ended: EventReader<ProxyGameEndedEvent>,
+    mut log: ResMut<ActionErrorLog>,
+    registry: Res<UnitRegistry>,
+) {
+    let mut new_entries = Vec::new();
+
+    for event in requests.read() {
+        let Some(Request_oneof_request::action(action)) = event.request.request.as_ref() else { continue; };
+        let game_loop = log.game_loops.get(&event.player_id).copied().unwrap_or(0);
+        let recent = log.recent_commands.entry(event.player_id).or_default();
+        recent.extend(action.get_actions().iter().filter_map(|a| ActionCommand::from_action(a, game_loop)));
+        let excess = recent.len().saturating_sub(RECENT_COMMANDS);
+        recent.drain(..excess);
+    }
+
+    // The proxy pairs each action request with its response; the n-th result answers the n-th action
+    for ProxyActionResultEvent(outcome) in action_results.read() {
+        let actions = outcome.request.get_actions();
+        let results = outcome.response.get_result();
+        if results.len() != actions.len() {
+            eprintln!("[action_errors] Player {} sent {} actions but got {} results at loop {}",
+                outcome.player_id, actions.len(), results.len(), outcome.game_loop);
+        }
+        for (index, &result) in results.iter().enumerate() {
+            if result == ActionResult::Success {
+                continue;
+            }
+            let command = actions.get(index).and_then(|a| ActionCommand::from_action(a, outcome.game_loop));
+            new_entries.push(ActionErrorEntry {
+                player_id: outcome.player_id,
+                game_loop: outcome.game_loop,
+                source: ActionErrorSource::Response,
+                result,
+                unit_tags: command.as_ref().map(|c| c.unit_tags.clone()).unwrap_or_default(),
+                command,
+            });
+        }
+    }
+
+    for event in responses.read() {
+        let Some(Response_oneof_response::observation(obs)) = event.response.response.as_ref() else { continue; };
+        let game_loop = obs.get_observation().get_game_loop();
+        log.game_loops.insert(event.player_id, game_loop);
+        for error in obs.get_action_errors() {
+            let command = log.find_command(event.player_id, error.get_unit_tag(), error.get_ability_id() as u32);
+            new_entries.push(ActionErrorEntry {
+                player_id: event.player_id,
+                game_loop,
+                source: ActionErrorSource::Observation,
+                result: error.get_result(),
+                command,
+                unit_tags: vec![error.get_unit_tag()],
+            });
+        }
+    }
+
+    if game_ended.read().last().is_some() {
+        log.reset_pending();
+    }
+
+    for entry in new_entries {
+        for tag in &entry.unit_tags {
+            if let Some(&entity) = registry.map.get(tag) {
+                // The unit may be despawned by `cleanup_dead_units` before the commands apply
+                commands.entity(entity).try_insert(ActionErrorFlash(Timer::from_seconds(FLASH_SECS, TimerMode::Once)));
+            }
+        }
+        if !log.paused {
+            log.push(entry);
+        }
+    }
+}
+
+/// Pulse flashing sprites red, then give them back their alliance tint
+pub fn action_error_flash_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut query: Query<(Entity, &mut ActionErrorFlash, &mut Sprite, &UnitAlliance)>,
+) {
+    for (entity, mut flash, mut sprite, alliance) in query.iter_mut() {
+        flash.0.tick(time.delta());
+        let base = alliance_color(alliance.0);
+        if flash.0.finished() {
+            sprite.color = base;
+            commands.entity(entity).try_remove::<ActionErrorFlash>();
+            continue;
+        }
+        // Three pulses over the flash duration
+        let pulse = (flash.0.fraction() * std::f32::consts::TAU * 3.0).cos() * 0.5 + 0.5;
+        sprite.color = base.mix(&Color::srgb(1.0, 0.1, 0.1), pulse);
+    }
+}
+
+/// Window listing failed actions, newest first; clicking one selects its unit
+pub fn action_errors_panel_system(
+    mut contexts: EguiContexts,
+    mut log: ResMut<ActionErrorLog>,
+    mut selected: ResMut<SelectedUnit>,
+    entity_system: Res<EntitySystem>,
+) {
+    let Ok(ctx) = contexts.ctx_mut() else { return; };
+
+    egui::Window::new("Action errors")
+        .default_open(false)
+        .default_width(460.0)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut log.paused, "Pause");
+                if ui.button("Clear").clicked() {
+                    log.entries.clear();
+                }
+                let mut filter = log.player_filter;
+                egui::ComboBox::from_id_salt("action_errors_player")
+                    .selected_text(filter.map(|p| format!("Player {}", p)).unwrap_or_else(|| "All players".to_string()))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut filter, None, "All players");
+                        ui.selectable_value(&mut filter, Some(1), "Player 1");
+                        ui.selectable_value(&mut filter, Some(2), "Player 2");
+                    });
+                log.player_filter = filter;
+            });
+
+            // Counts per result, most frequent first
+            let mut counts: HashMap<String, usize> = HashMap::new();
+            for entry in log.entries.iter().filter(|e| log.player_filter.is_none_or(|p| p == e.player_id)) {
+                *counts.entry(format!("{:?}", entry.result)).or_default() += 1;
+            }
+            let mut counts: Vec<(String, usize)> = counts.into_iter().collect();
+            counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+            if counts.is_empty() {
+                ui.label("No failed actions.");
+                return;
+            }
+            ui.label(counts.iter().map(|(r, n)| format!("{} ×{}", r, n)).collect::<Vec<_>>().join("  "));
+            ui.separator();
+
+            egui::ScrollArea::vertical().max_height(360.0).auto_shrink([false, false]).show(ui, |ui| {
+                for entry in log.entries.iter().rev().filter(|e| log.player_filter.is_none_or(|p| p == e.player_id)) {
+                    let source = match entry.source {
+                        ActionErrorSource::Response => "sent",
+                        ActionErrorSource::Observation => "executing",
+                    };
+                    let command = entry.command.as_ref()
+                        .map(|c| c.describe(&entity_system))
+                        .unwrap_or_else(|| format!("units {:?}", entry.unit_tags));
+                    let text = format!("loop {:<6} P{} {:?} ({}): {}", entry.game_loop, entry.player_id, entry.result, source, command);
+                    let response = ui.selectable_label(false, egui::RichText::new(text).color(egui::Color32::from_rgb(255, 130, 110)));
+                    if response.clicked() {
+                        if let Some(&tag) = entry.unit_tags.first() {
+                            selected.tag = Some(tag);
+                        }
+                    }
+                }
+            });
+        });
+}
@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 use bevy::prelude::{Resource, Color};
 use serde::{Deserialize, Serialize};
@@ -13,6 +14,25 @@ pub struct AppSettings {
     #[serde(skip)]
     pub unit_by_id: std::collections::HashMap<u32, DisplayInfo>,
     pub game_config_panel: GameConfigPanelDefaults,
+    #[serde(default)]
+    pub trace: TraceConfig,
+    #[serde(default)]
+    pub ladder: LadderConfig,
+    #[serde(default)]
+    pub bot_runner: BotRunnerConfig,
+    #[serde(default)]
+    pub matches: MatchesConfig,
+    #[serde(default)]
+    pub resource_limits: ResourceLimitsConfig,
+    #[serde(default)]
+    pub latency: LatencyConfig,
+    #[serde(default)]
+    pub inspector: InspectorConfig,
+    #[serde(default)]
+    pub overlays: OverlayConfig,
+    /// Bots that can be picked by name instead of typing a bash command
+    #[serde(default)]
+    pub bots: Vec<BotDefinition>,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, Default)]
@@ -95,6 +115,15 @@ impl Default for AppSettings {
             entity_display: EntityDisplayConfig::default(),
             unit_by_id: std::collections::HashMap::new(),
             game_config_panel: GameConfigPanelDefaults::default(),
+            trace: TraceConfig::default(),
+            ladder: LadderConfig::default(),
+            bot_runner: BotRunnerConfig::default(),
+            matches: MatchesConfig::default(),
+            resource_limits: ResourceLimitsConfig::default(),
+            latency: LatencyConfig::default(),
+            inspector: InspectorConfig::default(),
+            overlays: OverlayConfig::default(),
+            bots: Vec::new(),
         }
     }
 }
@@ -139,6 +168,15 @@ pub fn load_settings() -> AppSettings {
         starcraft: settings.starcraft,
         config_path: settings.config_path,
         game_config_panel: settings.game_config_panel,
+        trace: settings.trace,
+        ladder: settings.ladder,
+        bot_runner: settings.bot_runner,
+        matches: settings.matches,
+        resource_limits: settings.resource_limits,
+        latency: settings.latency,
+        inspector: settings.inspector,
+        overlays: settings.overlays,
+        bots: settings.bots,
     }
 }
 
@@ -167,6 +205,25 @@ pub struct StarcraftConfig {
     pub listen_port: u16,
     pub image: String,
     pub container_name: String,
+    /// Ports of additional SC2 instances, one per extra bot client (e.g. the opponent in VsBot)
+    #[serde(default)]
+    pub extra_upstream_ports: Vec<u16>,
+    /// Serve the upstream ports with the in-process mock server instead of starting Docker
+    #[serde(default)]
+    pub mock: bool,
+    /// Session trace whose map and observations the mock server replays; a synthetic map otherwise
+    #[serde(default)]
+    pub mock_trace: Option<String>,
+}
+
+impl StarcraftConfig {
+    /// Upstream websocket URLs, one per bot client the proxy accepts
+    pub fn upstream_urls(&self) -> Vec<String> {
+        std::iter::once(self.upstream_port)
+            .chain(self.extra_upstream_ports.iter().copied())
+            .map(|port| format!("{}:{}/sc2api", self.upstream_url, port))
+            .collect()
+    }
 }
 
 impl Default for StarcraftConfig {
@@ -178,21 +235,288 @@ impl Default for StarcraftConfig {
             listen_port: 5000,
             image: "minimal-sc2:latest".to_string(),
             container_name: "aiurgaze-sc2".to_string(),
+            extra_upstream_ports: Vec::new(),
+            mock: false,
+            mock_trace: None,
         }
     }
 }
 
+/// A bot taking part in the local ladder
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct LadderBot {
+    pub name: String,
+    #[serde(default)]
+    pub race: Option<String>,
+    /// Bash command launching the bot
+    pub command: String,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, Default, PartialEq, Eq)]
+pub enum PairingMode {
+    #[default]
+    RoundRobin,
+    Swiss,
+}
+
+/// Local bot-vs-bot ladder: roster, schedule and rating storage
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct LadderConfig {
+    pub bots: Vec<LadderBot>,
+    pub maps: Vec<String>,
+    pub pairing: PairingMode,
+    /// Round-robin cycles, or Swiss rounds
+    pub rounds: u32,
+    pub k_factor: f64,
+    pub ratings_file: String,
+}
+
+impl Default for LadderConfig {
+    fn default() -> Self {
+        Self {
+            bots: Vec::new(),
+            maps: Vec::new(),
+            pairing: PairingMode::RoundRobin,
+            rounds: 1,
+            k_factor: 32.0,
+            ratings_file: "ladder_ratings.json".to_string(),
+        }
+    }
+}
+
+/// Recording of proxied sessions to disk
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TraceConfig {
+    pub enabled: bool,
+    pub dir: String,
+}
+
+impl Default for TraceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            dir: "traces".to_string(),
+        }
+    }
+}
+
+/// Per-match directories holding bot logs, traces, the CreateGame request and the result
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct MatchesConfig {
+    pub enabled: bool,
+    pub dir: String,
+    /// Matches listed on the start screen
+    pub recent: usize,
+}
+
+impl Default for MatchesConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            dir: "matches".to_string(),
+            recent: 20,
+        }
+    }
+}
+
+/// Sampling of bot CPU, memory and threads, and the levels that raise a warning (0 = no limit)
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ResourceLimitsConfig {
+    pub sample_interval_ms: u64,
+    pub max_rss_mb: f64,
+    pub max_cpu_percent: f64,
+    pub max_threads: u64,
+}
+
+impl Default for ResourceLimitsConfig {
+    fn default() -> Self {
+        Self {
+            sample_interval_ms: 1000,
+            max_rss_mb: 0.0,
+            max_cpu_percent: 0.0,
+            max_threads: 0,
+        }
+    }
+}
+
+/// Step timing measured by the proxy
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+#[serde(default)]
+pub struct LatencyConfig {
+    /// Bot step time a ladder would tolerate, highlighted in the latency panel (0 = none)
+    pub step_limit_ms: f64,
+}
+
+/// Protocol inspector window
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct InspectorConfig {
+    /// Copy every proxied message for the inspector. Off by default since it holds whole
+    /// observations; never on with `--headless`.
+    pub enabled: bool,
+    /// Messages kept in the inspector; older ones are dropped
+    pub max_messages: usize,
+}
+
+impl Default for InspectorConfig {
+    fn default() -> Self {
+        Self { enabled: false, max_messages: 2000 }
+    }
+}
+
+/// What the bots asked for, drawn over the map
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct OverlayConfig {
+    /// Draw the raw commands each bot sends
+    pub bot_actions: bool,
+    /// Seconds a command stays on screen after it was sent
+    pub bot_action_secs: f32,
+    /// Draw the text, lines, boxes and spheres bots send with RequestDebug
+    pub debug_draw: bool,
+}
+
+impl Default for OverlayConfig {
+    fn default() -> Self {
+        Self { bot_actions: true, bot_action_secs: 1.5, debug_draw: true }
+    }
+}
+
+/// Limits applied to the bot processes aiurgaze launches
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct BotRunnerConfig {
+    /// Seconds a bot gets to connect to the proxy before it is killed; 0 disables the check
+    pub startup_timeout_secs: u64,
+    /// Seconds a bot may run in total before it is killed; 0 means no limit
+    pub max_game_secs: u64,
+    /// Kill the bots' process groups when the game ends
+    pub kill_on_game_end: bool,
+}
+
+impl Default for BotRunnerConfig {
+    fn default() -> Self {
+        Self {
+            startup_timeout_secs: 60,
+            max_game_secs: 0,
+            kill_on_game_end: true,
+        }
+    }
+}
+
+/// A bot launched directly from its executable, declared as a `[[bots]]` entry
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct BotDefinition {
+    pub name: String,
+    #[serde(default)]
+    pub race: Option<String>,
+    #[serde(default)]
+    pub working_dir: Option<String>,
+    pub executable: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// Arguments telling the bot where to connect; `{host}` and `{port}` are replaced with the proxy address
+    #[serde(default = "default_connect_args")]
+    pub connect_args: Vec<String>,
+}
+
+fn default_connect_args() -> Vec<String> {
+    ["--LadderServer", "{host}", "--GamePort", "{port}"].map(String::from).to_vec()
+}
+
+impl BotDefinition {
+    /// Full argument list with the proxy address filled into the connect arguments
+    pub fn resolved_args(&self, host: &str, port: u16) -> Vec<String> {
+        self.args.iter().cloned()
+            .chain(self.connect_args.iter().map(|arg| arg.replace("{host}", host).replace("{port}", &port.to_string())))
+            .collect()
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize, Default)]
 pub struct GameConfigPanelDefaults {
     pub game_type: Option<String>,
     pub map_name: Option<String>,
     pub player_name: Option<String>,
+    pub player_race: Option<String>,
     pub ai_difficulty: Option<String>,
     pub ai_race: Option<String>,
+    pub ai_build: Option<String>,
     pub bot_name: Option<String>,
     pub disable_fog: Option<bool>,
     pub random_seed: Option<u32>,
     pub realtime: Option<bool>,
     pub bot_command: Option<String>,
     pub bot_opponent_command: Option<String>,
+    /// Name of a `[[bots]]` entry used for the player bot instead of `bot_command`
+    pub player_bot: Option<String>,
+    /// Name of a `[[bots]]` entry used for the opponent bot instead of `bot_opponent_command`
+    pub opponent_bot: Option<String>,
+    pub auto_restart: Option<bool>,
+}
+
+/// Choices made in the game config panel, remembered across runs
+pub const LAST_GAME_PRESET: &str = "last_game.toml";
+
+impl GameConfigPanelDefaults {
+    /// Load a saved game preset (same keys as `[game_config_panel]`)
+    pub fn load_preset(path: &std::path::Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read preset {}: {}", path.display(), e))?;
+        toml::from_str(&content)
+            .map_err(|e| format!("Failed to parse preset {}: {}", path.display(), e))
+    }
+
+    /// Write these settings as a preset that `load_preset` can read back
+    pub fn save_preset(&self, path: &std::path::Path) -> Result<(), String> {
+        let content = toml::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize preset: {}", e))?;
+        std::fs::write(path, content)
+            .map_err(|e| format!("Failed to write preset {}: {}", path.display(), e))
+    }
+
+    /// These defaults with the choices of the last game created from the start screen on top, if any
+    pub fn with_last_game(self) -> Self {
+        let last_game = std::path::Path::new(LAST_GAME_PRESET);
+        if !last_game.exists() {
+            return self;
+        }
+        match Self::load_preset(last_game) {
+            Ok(preset) => {
+                println!("[config] Start screen restores the last game from {} (delete it to use config.toml)", LAST_GAME_PRESET);
+                self.overridden_by(preset)
+            }
+            Err(e) => {
+                eprintln!("[config] {}", e);
+                self
+            }
+        }
+    }
+
+    /// Fields set in `preset` take precedence over these defaults
+    pub fn overridden_by(self, preset: GameConfigPanelDefaults) -> Self {
+        Self {
+            game_type: preset.game_type.or(self.game_type),
+            map_name: preset.map_name.or(self.map_name),
+            player_name: preset.player_name.or(self.player_name),
+            player_race: preset.player_race.or(self.player_race),
+            ai_difficulty: preset.ai_difficulty.or(self.ai_difficulty),
+            ai_race: preset.ai_race.or(self.ai_race),
+            ai_build: preset.ai_build.or(self.ai_build),
+            bot_name: preset.bot_name.or(self.bot_name),
+            disable_fog: preset.disable_fog.or(self.disable_fog),
+            random_seed: preset.random_seed.or(self.random_seed),
+            realtime: preset.realtime.or(self.realtime),
+            bot_command: preset.bot_command.or(self.bot_command),
+            bot_opponent_command: preset.bot_opponent_command.or(self.bot_opponent_command),
+            player_bot: preset.player_bot.or(self.player_bot),
+            opponent_bot: preset.opponent_bot.or(self.opponent_bot),
+            auto_restart: preset.auto_restart.or(self.auto_restart),
+        }
+    }
 }
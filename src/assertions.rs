@@ -0,0 +1,460 @@
+// src/assertions.rs
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use sc2_proto::raw::Alliance;
+use sc2_proto::sc2api::{ResponseObservation, Result as GameResult};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use crate::controller::{ProxyGameEndedEvent, ProxyResponseEvent};
+use crate::entity_system::load_unit_type_ids;
+use crate::scenario::UnitRef;
+
+/// Game result a spec expects, named case-insensitively; anything else fails to load
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "String")]
+pub enum ExpectedResult {
+    Victory,
+    Defeat,
+    Tie,
+    Undecided,
+}
+
+impl TryFrom<String> for ExpectedResult {
+    type Error = String;
+
+    fn try_from(result: String) -> Result<Self, String> {
+        match result.to_lowercase().as_str() {
+            "victory" => Ok(ExpectedResult::Victory),
+            "defeat" => Ok(ExpectedResult::Defeat),
+            "tie" => Ok(ExpectedResult::Tie),
+            "undecided" => Ok(ExpectedResult::Undecided),
+            _ => Err(format!("Invalid result '{}'. Allowed results: Victory, Defeat, Tie, Undecided", result)),
+        }
+    }
+}
+
+impl ExpectedResult {
+    fn matches(self, actual: GameResult) -> bool {
+        matches!(
+            (self, actual),
+            (ExpectedResult::Victory, GameResult::Victory)
+                | (ExpectedResult::Defeat, GameResult::Defeat)
+                | (ExpectedResult::Tie, GameResult::Tie)
+                | (ExpectedResult::Undecided, GameResult::Undecided)
+        )
+    }
+}
+
+/// One expectation of a test spec
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type")]
+pub enum Expectation {
+    /// By `by_loop`, the player has at least `count` finished units of these types (e.g. its townhalls)
+    UnitCount {
+        player_id: u32,
+        units: Vec<UnitRef>,
+        count: u32,
+        by_loop: u32,
+    },
+    /// No unit of these types dies before `before_loop`; only the player's units if `player_id` is set
+    NoDeaths {
+        #[serde(default)]
+        player_id: Option<u32>,
+        units: Vec<UnitRef>,
+        before_loop: u32,
+    },
+    /// The game ends with this result for the player
+    Result {
+        player_id: u32,
+        result: ExpectedResult,
+    },
+}
+
+impl Expectation {
+    pub fn describe(&self) -> String {
+        let names = |units: &[UnitRef]| units.iter()
+            .map(|u| match u {
+                UnitRef::Id(id) => id.to_string(),
+                UnitRef::Name(name) => name.clone(),
+            })
+            .collect::<Vec<_>>()
+            .join("/");
+        match self {
+            Expectation::UnitCount { player_id, units, count, by_loop } =>
+                format!("player {} has >= {} {} by loop {}", player_id, count, names(units), by_loop),
+            Expectation::NoDeaths { player_id: Some(player_id), units, before_loop } =>
+                format!("no {} of player {} dies before loop {}", names(units), player_id, before_loop),
+            Expectation::NoDeaths { player_id: None, units, before_loop } =>
+                format!("no {} dies before loop {}", names(units), before_loop),
+            Expectation::Result { player_id, result } =>
+                format!("game ends in {:?} for player {}", result, player_id),
+        }
+    }
+}
+
+/// Test spec, loaded from a TOML file
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TestSpec {
+    #[serde(rename = "expect")]
+    pub expectations: Vec<Expectation>,
+    /// Where the JSON report is written, if anywhere
+    #[serde(default)]
+    pub report: Option<String>,
+}
+
+impl TestSpec {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read test spec {}: {}", path.display(), e))?;
+        let spec: TestSpec = toml::from_str(&content)
+            .map_err(|e| format!("Failed to parse test spec {}: {}", path.display(), e))?;
+        if spec.expectations.is_empty() {
+            return Err(format!("Test spec {} has no [[expect]] entries", path.display()));
+        }
+        Ok(spec)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "status")]
+pub enum Outcome {
+    Pending,
+    Passed { game_loop: u32 },
+    Failed { game_loop: u32, reason: String },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExpectationReport {
+    pub expectation: String,
+    pub outcome: Outcome,
+}
+
+/// An expectation with its unit names resolved and its progress so far
+struct Check {
+    expectation: Expectation,
+    unit_types: HashSet<u32>,
+    outcome: Outcome,
+    /// Best unit count seen, for the failure message
+    best_count: u32,
+}
+
+/// Evaluates a spec against observations, live or from a trace
+pub struct ExpectationRun {
+    checks: Vec<Check>,
+    /// Type and owner of every unit seen, to tell what the dead units were
+    known_units: HashMap<u64, (u32, i32)>,
+    /// Latest loop each player observed; a reported death happened after the previous one
+    player_loops: HashMap<u32, u32>,
+    last_loop: u32,
+    report_path: Option<String>,
+}
+
+impl ExpectationRun {
+    pub fn new(spec: TestSpec) -> Result<Self, String> {
+        let needs_names = spec.expectations.iter().any(|e| match e {
+            Expectation::UnitCount { units, .. } | Expectation::NoDeaths { units, .. } =>
+                units.iter().any(|u| matches!(u, UnitRef::Name(_))),
+            Expectation::Result { .. } => false,
+        });
+        let ids = if needs_names { load_unit_type_ids()? } else { HashMap::new() };
+
+        let mut checks = Vec::new();
+        for expectation in spec.expectations {
+            let unit_types = match &expectation {
+                Expectation::UnitCount { units, .. } | Expectation::NoDeaths { units, .. } =>
+                    units.iter().map(|u| u.resolve(&ids)).collect::<Result<HashSet<u32>, String>>()?,
+                Expectation::Result { .. } => HashSet::new(),
+            };
+            checks.push(Check { expectation, unit_types, outcome: Outcome::Pending, best_count: 0 });
+        }
+        Ok(Self { checks, known_units: HashMap::new(), player_loops: HashMap::new(), last_loop: 0, report_path: spec.report })
+    }
+
+    pub fn is_resolved(&self) -> bool {
+        self.checks.iter().all(|c| c.outcome != Outcome::Pending)
+    }
+
+    /// Evaluate the pending expectations against one player's observation
+    pub fn observe(&mut self, player_id: u32, response: &ResponseObservation) {
+        let observation = response.get_observation();
+        let game_loop = observation.get_game_loop();
+        self.last_loop = self.last_loop.max(game_loop);
+        let previous_loop = self.player_loops.insert(player_id, game_loop).unwrap_or(0);
+        let earliest_loop = self.player_loops.values().copied().min().unwrap_or(game_loop);
+        let raw = observation.get_raw_data();
+        for unit in raw.get_units() {
+            self.known_units.insert(unit.get_tag(), (unit.get_unit_type(), unit.get_owner()));
+        }
+        let dead: Vec<(u64, u32, i32)> = raw.get_event().get_dead_units().iter()
+            .filter_map(|tag| self.known_units.get(tag).map(|(unit_type, owner)| (*tag, *unit_type, *owner)))
+            .collect();
+
+        for check in self.checks.iter_mut().filter(|c| c.outcome == Outcome::Pending) {
+            match &check.expectation {
+                Expectation::UnitCount { player_id: expected_player, count, by_loop, .. } => {
+                    if *expected_player != player_id {
+                        continue;
+                    }
+                    let have = raw.get_units().iter()
+                        .filter(|u| u.get_alliance() == Alliance::value_Self && u.get_build_progress() >= 1.0)
+                        .filter(|u| check.unit_types.contains(&u.get_unit_type()))
+                        .count() as u32;
+                    check.best_count = check.best_count.max(have);
+                    if game_loop <= *by_loop && have >= *count {
+                        check.outcome = Outcome::Passed { game_loop };
+                    } else if game_loop >= *by_loop {
+                        check.outcome = Outcome::Failed {
+                            game_loop,
+                            reason: format!("had at most {} by loop {}", check.best_count, by_loop),
+                        };
+                    }
+                }
+                Expectation::NoDeaths { player_id: owner, before_loop, .. } => {
+                    // Deaths are reported with the observation that follows them, so a death
+                    // reported now may have happened any time after this player's previous observation
+                    let died = dead.iter().find(|(_, unit_type, unit_owner)| {
+                        check.unit_types.contains(unit_type) && owner.is_none_or(|o| o as i32 == *unit_owner)
+                    });
+                    if let Some((tag, unit_type, _)) = died.filter(|_| previous_loop < *before_loop) {
+                        check.outcome = Outcome::Failed {
+                            game_loop,
+                            reason: format!("unit {} of type {} died", tag, unit_type),
+                        };
+                    } else if earliest_loop >= *before_loop {
+                        // Every player is past the deadline, so no earlier death can still be reported
+                        check.outcome = Outcome::Passed { game_loop };
+                    }
+                }
+                Expectation::Result { player_id: expected_player, result } => {
+                    let Some(actual) = response.get_player_result().iter().find(|r| r.get_player_id() == *expected_player) else {
+                        continue;
+                    };
+                    check.outcome = if result.matches(actual.get_result()) {
+                        Outcome::Passed { game_loop }
+                    } else {
+                        Outcome::Failed { game_loop, reason: format!("game ended in {:?}", actual.get_result()) }
+                    };
+                }
+            }
+        }
+    }
+
+    /// Settle the expectations still pending when the game or trace ends
+    pub fn finish(&mut self) -> Vec<ExpectationReport> {
+        let last_loop = self.last_loop;
+        for check in self.checks.iter_mut().filter(|c| c.outcome == Outcome::Pending) {
+            check.outcome = match &check.expectation {
+                Expectation::UnitCount { .. } => Outcome::Failed {
+                    game_loop: last_loop,
+                    reason: format!("game ended with at most {}", check.best_count),
+                },
+                Expectation::NoDeaths { .. } => Outcome::Passed { game_loop: last_loop },
+                Expectation::Result { .. } => Outcome::Failed {
+                    game_loop: last_loop,
+                    reason: "game ended without a result".to_string(),
+                },
+            };
+        }
+        self.checks.iter()
+            .map(|c| ExpectationReport { expectation: c.expectation.describe(), outcome: c.outcome.clone() })
+            .collect()
+    }
+
+    /// Print the report, write it if the spec asks to, and tell whether everything passed
+    pub fn report(&mut self) -> bool {
+        let reports = self.finish();
+        println!("[assertions] Test report:");
+        for report in &reports {
+            match &report.outcome {
+                Outcome::Passed { game_loop } => println!("  PASS  {} (loop {})", report.expectation, game_loop),
+                Outcome::Failed { game_loop, reason } => println!("  FAIL  {}: {} (loop {})", report.expectation, reason, game_loop),
+                Outcome::Pending => println!("  ????  {}", report.expectation),
+            }
+        }
+        let failed = reports.iter().filter(|r| matches!(r.outcome, Outcome::Failed { .. })).count();
+        println!("[assertions] {} passed, {} failed", reports.len() - failed, failed);
+
+        if let Some(path) = &self.report_path {
+            let result = serde_json::to_string_pretty(&reports)
+                .map_err(|e| e.to_string())
+                .and_then(|json| fs::write(path, json).map_err(|e| e.to_string()));
+            match result {
+                Ok(_) => println!("[assertions] Wrote report to {}", path),
+                Err(e) => eprintln!("[assertions] Failed to write {}: {}", path, e),
+            }
+        }
+        failed == 0
+    }
+}
+
+/// Resource running a test spec against the live game
+#[derive(Resource)]
+pub struct ExpectationRunner(pub ExpectationRun);
+
+/// System to evaluate the spec on every observation and exit with the result once the game ends,
+/// or as soon as every expectation is settled
+pub fn expectation_system(
+    mut responses: EventReader<ProxyResponseEvent>,
+    mut game_ended: EventReader<ProxyGameEndedEvent>,
+    mut runner: ResMut<ExpectationRunner>,
+    mut exit: EventWriter<AppExit>,
+    mut done: Local<bool>,
+) {
+    if *done {
+        return;
+    }
+    for event in responses.read() {
+        if event.response.has_observation() {
+            runner.0.observe(event.player_id, event.response.get_observation());
+        }
+    }
+    if game_ended.read().last().is_some() || runner.0.is_resolved() {
+        *done = true;
+        let passed = runner.0.report();
+        exit.write(if passed { AppExit::Success } else { AppExit::from_code(1) });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sc2_proto::raw::Unit;
+    use sc2_proto::sc2api::PlayerResult;
+
+    const MARINE: u32 = 48;
+    const COMMAND_CENTER: u32 = 18;
+
+    fn run(expectations: Vec<Expectation>) -> ExpectationRun {
+        ExpectationRun::new(TestSpec { expectations, report: None }).unwrap()
+    }
+
+    fn unit(tag: u64, unit_type: u32, owner: i32, build_progress: f32) -> Unit {
+        let mut unit = Unit::new();
+        unit.set_tag(tag);
+        unit.set_unit_type(unit_type);
+        unit.set_owner(owner);
+        unit.set_alliance(if owner == 1 { Alliance::value_Self } else { Alliance::Enemy });
+        unit.set_build_progress(build_progress);
+        unit
+    }
+
+    fn observation(game_loop: u32, units: Vec<Unit>, dead: Vec<u64>) -> ResponseObservation {
+        let mut response = ResponseObservation::new();
+        let observation = response.mut_observation();
+        observation.set_game_loop(game_loop);
+        let raw = observation.mut_raw_data();
+        for unit in units {
+            raw.mut_units().push(unit);
+        }
+        raw.mut_event().set_dead_units(dead);
+        response
+    }
+
+    fn outcomes(run: &mut ExpectationRun) -> Vec<Outcome> {
+        run.finish().into_iter().map(|r| r.outcome).collect()
+    }
+
+    #[test]
+    fn unit_count_passes_once_enough_units_are_finished() {
+        let mut run = run(vec![Expectation::UnitCount {
+            player_id: 1,
+            units: vec![UnitRef::Id(COMMAND_CENTER)],
+            count: 2,
+            by_loop: 100,
+        }]);
+        run.observe(1, &observation(40, vec![unit(1, COMMAND_CENTER, 1, 1.0), unit(2, COMMAND_CENTER, 1, 0.5)], vec![]));
+        assert!(!run.is_resolved());
+        run.observe(1, &observation(80, vec![unit(1, COMMAND_CENTER, 1, 1.0), unit(2, COMMAND_CENTER, 1, 1.0)], vec![]));
+        assert_eq!(outcomes(&mut run), vec![Outcome::Passed { game_loop: 80 }]);
+    }
+
+    #[test]
+    fn unit_count_fails_past_the_deadline() {
+        let mut run = run(vec![Expectation::UnitCount {
+            player_id: 1,
+            units: vec![UnitRef::Id(COMMAND_CENTER)],
+            count: 2,
+            by_loop: 100,
+        }]);
+        run.observe(1, &observation(96, vec![unit(1, COMMAND_CENTER, 1, 1.0)], vec![]));
+        run.observe(1, &observation(104, vec![unit(1, COMMAND_CENTER, 1, 1.0), unit(2, COMMAND_CENTER, 1, 1.0)], vec![]));
+        assert!(matches!(outcomes(&mut run)[..], [Outcome::Failed { game_loop: 104, .. }]));
+    }
+
+    #[test]
+    fn death_reported_after_the_deadline_still_fails() {
+        let mut run = run(vec![Expectation::NoDeaths { player_id: Some(1), units: vec![UnitRef::Id(MARINE)], before_loop: 100 }]);
+        run.observe(1, &observation(96, vec![unit(7, MARINE, 1, 1.0)], vec![]));
+        // Died at some loop in 97..=104, only known to be after 96
+        run.observe(1, &observation(104, vec![], vec![7]));
+        assert!(matches!(outcomes(&mut run)[..], [Outcome::Failed { game_loop: 104, .. }]));
+    }
+
+    #[test]
+    fn deaths_after_the_deadline_or_of_other_units_pass() {
+        let mut run = run(vec![Expectation::NoDeaths { player_id: Some(1), units: vec![UnitRef::Id(MARINE)], before_loop: 100 }]);
+        run.observe(1, &observation(8, vec![unit(7, MARINE, 1, 1.0), unit(8, MARINE, 2, 1.0), unit(9, COMMAND_CENTER, 1, 1.0)], vec![]));
+        run.observe(1, &observation(16, vec![unit(7, MARINE, 1, 1.0)], vec![8, 9]));
+        assert!(!run.is_resolved());
+        run.observe(1, &observation(104, vec![unit(7, MARINE, 1, 1.0)], vec![]));
+        assert!(run.is_resolved());
+        run.observe(1, &observation(112, vec![], vec![7]));
+        assert_eq!(outcomes(&mut run), vec![Outcome::Passed { game_loop: 104 }]);
+    }
+
+    #[test]
+    fn no_deaths_waits_for_every_player() {
+        let mut run = run(vec![Expectation::NoDeaths { player_id: None, units: vec![UnitRef::Id(MARINE)], before_loop: 100 }]);
+        run.observe(1, &observation(96, vec![unit(7, MARINE, 1, 1.0)], vec![]));
+        run.observe(2, &observation(96, vec![unit(7, MARINE, 1, 1.0)], vec![]));
+        run.observe(1, &observation(104, vec![], vec![]));
+        assert!(!run.is_resolved());
+        run.observe(2, &observation(104, vec![], vec![7]));
+        assert!(matches!(outcomes(&mut run)[..], [Outcome::Failed { .. }]));
+    }
+
+    #[test]
+    fn result_is_read_from_the_final_observation() {
+        let mut run = run(vec![
+            Expectation::Result { player_id: 1, result: ExpectedResult::Victory },
+            Expectation::Result { player_id: 2, result: ExpectedResult::Victory },
+        ]);
+        let mut last = observation(500, vec![], vec![]);
+        for (player_id, result) in [(1, GameResult::Victory), (2, GameResult::Defeat)] {
+            let mut player_result = PlayerResult::new();
+            player_result.set_player_id(player_id);
+            player_result.set_result(result);
+            last.mut_player_result().push(player_result);
+        }
+        run.observe(1, &last);
+        let outcomes = outcomes(&mut run);
+        assert_eq!(outcomes[0], Outcome::Passed { game_loop: 500 });
+        assert!(matches!(&outcomes[1], Outcome::Failed { reason, .. } if reason == "game ended in Defeat"));
+    }
+
+    #[test]
+    fn pending_checks_settle_when_the_game_ends() {
+        let mut run = run(vec![
+            Expectation::UnitCount { player_id: 1, units: vec![UnitRef::Id(COMMAND_CENTER)], count: 1, by_loop: 1000 },
+            Expectation::NoDeaths { player_id: None, units: vec![UnitRef::Id(MARINE)], before_loop: 1000 },
+            Expectation::Result { player_id: 1, result: ExpectedResult::Victory },
+        ]);
+        run.observe(1, &observation(50, vec![], vec![]));
+        let outcomes = outcomes(&mut run);
+        assert!(matches!(outcomes[0], Outcome::Failed { .. }));
+        assert_eq!(outcomes[1], Outcome::Passed { game_loop: 50 });
+        assert!(matches!(&outcomes[2], Outcome::Failed { reason, .. } if reason == "game ended without a result"));
+    }
+
+    #[test]
+    fn results_are_parsed_when_the_spec_loads() {
+        let spec = |result: &str| toml::from_str::<TestSpec>(&format!(
+            "[[expect]]\ntype = \"Result\"\nplayer_id = 1\nresult = \"{}\"\n", result
+        ));
+        let parsed = spec("victory").unwrap();
+        assert!(matches!(parsed.expectations[..], [Expectation::Result { result: ExpectedResult::Victory, .. }]));
+        let error = spec("Win").unwrap_err().to_string();
+        assert!(error.contains("Invalid result 'Win'"), "{}", error);
+    }
+}
@@ -0,0 +1,278 @@
+// src/bot_actions.rs
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use sc2_proto::raw::ActionRaw_oneof_action;
+use sc2_proto::sc2api::{Action, Request_oneof_request};
+use crate::action_errors::ActionTarget;
+use crate::app_settings::AppSettings;
+use crate::controller::{visible_for, MapResource, PlayerPerspective, ProxyGameEndedEvent, ProxyRequestEvent, ProxyResponseEvent};
+use crate::debug_draw::DebugDrawOverlay;
+use crate::entity_system::EntitySystem;
+use crate::units::{world_position, UnitRegistry, UnitTag};
+
+/// Overlays kept at once; the oldest go first when a bot floods commands
+const MAX_ACTIONS: usize = 400;
+/// Size of the in-game camera view in map cells, roughly
+const CAMERA_VIEW: Vec2 = Vec2::new(24.0, 14.0);
+
+/// One raw action as the bot sent it
+#[derive(Debug, Clone)]
+pub enum IssuedCommand {
+    Unit { ability_id: u32, unit_tags: Vec<u64>, target: ActionTarget },
+    Camera { x: f32, y: f32 },
+    Autocast { ability_id: u32, unit_tags: Vec<u64> },
+}
+
+#[derive(Debug)]
+pub struct IssuedAction {
+    pub player_id: u32,
+    pub game_loop: u32,
+    pub command: IssuedCommand,
+    timer: Timer,
+    label: Option<Entity>,
+}
+
+/// Resource holding the commands drawn over the map until their timer runs out
+#[derive(Resource)]
+pub struct BotActionOverlay {
+    pub actions: Vec<IssuedAction>,
+    pub enabled: bool,
+    pub lifetime_secs: f32,
+    game_loop: u32,
+}
+
+impl BotActionOverlay {
+    pub fn new(settings: &AppSettings) -> Self {
+        Self {
+            actions: Vec::new(),
+            enabled: settings.overlays.bot_actions,
+            lifetime_secs: settings.overlays.bot_action_secs,
+            game_loop: 0,
+        }
+    }
+}
+
+/// Text label of an overlay, despawned with it
+#[derive(Component)]
+pub struct BotActionLabel;
+
+fn player_color(player_id: u32) -> Color {
+    match player_id {
+        1 => Color::srgb(0.4, 0.85, 1.0),
+        _ => Color::srgb(1.0, 0.65, 0.25),
+    }
+}
+
+fn parse_action(action: &Action) -> Option<IssuedCommand> {
+    match action.get_action_raw().action.as_ref()? {
+        ActionRaw_oneof_action::unit_command(cmd) => Some(IssuedCommand::Unit {
+            ability_id: cmd.get_ability_id() as u32,
+            unit_tags: cmd.get_unit_tags().to_vec(),
+            target: ActionTarget::of(cmd),
+        }),
+        ActionRaw_oneof_action::camera_move(camera) => {
+            let center = camera.get_center_world_space();
+            Some(IssuedCommand::Camera { x: center.get_x(), y: center.get_y() })
+        }
+        ActionRaw_oneof_action::toggle_autocast(toggle) => Some(IssuedCommand::Autocast {
+            ability_id: toggle.get_ability_id() as u32,
+            unit_tags: toggle.get_unit_tags().to_vec(),
+        }),
+    }
+}
+
+fn ability_label(entity_system: &EntitySystem, ability_id: u32) -> String {
+    entity_system.ability_name(ability_id)
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("ability {}", ability_id))
+}
+
+/// System to turn the bots' action requests into overlays, and expire them
+pub fn bot_action_capture_system(
+    mut commands: Commands,
+    mut requests: EventReader<ProxyRequestEvent>,
+    mut responses: EventReader<ProxyResponseEvent>,
+    mut game_ended: EventReader<ProxyGameEndedEvent>,
+    mut overlay: ResMut<BotActionOverlay>,
+    mut labels: Query<(&mut TextColor, &mut Visibility), With<BotActionLabel>>,
+    units: Query<&Transform, With<UnitTag>>,
+    registry: Res<UnitRegistry>,
+    map_res: Option<Res<MapResource>>,
+    entity_system: Res<EntitySystem>,
+    perspective: Res<PlayerPerspective>,
+    time: Res<Time>,
+) {
+    // Requests carry no game loop, so overlays are stamped with the latest observed one
+    for event in responses.read() {
+        if event.response.has_observation() {
+            overlay.game_loop = event.response.get_observation().get_observation().get_game_loop();
+        }
+    }
+    if game_ended.read().last().is_some() {
+        for action in overlay.actions.drain(..) {
+            if let Some(label) = action.label {
+                commands.entity(label).despawn();
+            }
+        }
+    }
+
+    let tile_size = entity_system.map_config.tile_size;
+    let map_size = map_res.as_ref().map(|m| {
+        let (w, h) = m.static_layers.get_dimensions();
+        (w as f32, h as f32)
+    });
+    let unit_position = |tag: u64| registry.map.get(&tag).and_then(|&e| units.get(e).ok()).map(|t| t.translation.truncate());
+
+    for event in requests.read() {
+        let Some(Request_oneof_request::action(request)) = event.request.request.as_ref() else { continue; };
+        if !overlay.enabled {
+            continue;
+        }
+        let Some(map_size) = map_size else { continue; };
+        let game_loop = overlay.game_loop;
+        for command in request.get_actions().iter().filter_map(parse_action) {
+            // Label the target if there is one, otherwise the first unit
+            let (text, position) = match &command {
+                IssuedCommand::Unit { ability_id, unit_tags, target } => {
+                    let at = match target {
+                        ActionTarget::Point(x, y) => Some(world_position(*x, *y, map_size, tile_size)),
+                        ActionTarget::Unit(tag) => unit_position(*tag),
+                        ActionTarget::None => None,
+                    };
+                    (ability_label(&entity_system, *ability_id), at.or_else(|| unit_tags.first().and_then(|t| unit_position(*t))))
+                }
+                IssuedCommand::Camera { x, y } => ("camera".to_string(), Some(world_position(*x, *y, map_size, tile_size))),
+                IssuedCommand::Autocast { ability_id, unit_tags } => (
+                    format!("autocast {}", ability_label(&entity_system, *ability_id)),
+                    unit_tags.first().and_then(|t| unit_position(*t)),
+                ),
+            };
+            let label = position.map(|pos| {
+                commands.spawn((
+                    Text2d::new(text),
+                    TextFont { font_size: 10.0, ..default() },
+                    TextColor(player_color(event.player_id)),
+                    Transform::from_translation((pos + Vec2::new(0.0, tile_size)).extend(5.0)),
+                    BotActionLabel,
+                )).id()
+            });
+            let lifetime = overlay.lifetime_secs.max(0.1);
+            overlay.actions.push(IssuedAction {
+                player_id: event.player_id,
+                game_loop,
+                command,
+                timer: Timer::from_seconds(lifetime, TimerMode::Once),
+                label,
+            });
+        }
+    }
+
+    let excess = overlay.actions.len().saturating_sub(MAX_ACTIONS);
+    for action in overlay.actions.drain(..excess) {
+        if let Some(label) = action.label {
+            commands.entity(label).despawn();
+        }
+    }
+
+    let delta = time.delta();
+    overlay.actions.retain_mut(|action| {
+        action.timer.tick(delta);
+        let Some(label) = action.label else { return !action.timer.finished(); };
+        if action.timer.finished() {
+            commands.entity(label).despawn();
+            return false;
+        }
+        if let Ok((mut color, mut visibility)) = labels.get_mut(label) {
+            color.0 = player_color(action.player_id).with_alpha(1.0 - action.timer.fraction());
+            *visibility = if visible_for(&perspective, action.player_id) { Visibility::Inherited } else { Visibility::Hidden };
+        }
+        true
+    });
+}
+
+/// System to draw each live overlay from the units that were commanded, fading out as it expires
+pub fn bot_action_draw_system(
+    mut gizmos: Gizmos,
+    overlay: Res<BotActionOverlay>,
+    units: Query<&Transform, With<UnitTag>>,
+    registry: Res<UnitRegistry>,
+    map_res: Option<Res<MapResource>>,
+    entity_system: Res<EntitySystem>,
+    perspective: Res<PlayerPerspective>,
+) {
+    let Some(map_res) = map_res else { return; };
+    let (w, h) = map_res.static_layers.get_dimensions();
+    let map_size = (w as f32, h as f32);
+    let tile_size = entity_system.map_config.tile_size;
+    let unit_position = |tag: u64| registry.map.get(&tag).and_then(|&e| units.get(e).ok()).map(|t| t.translation.truncate());
+
+    for action in overlay.actions.iter().filter(|a| visible_for(&perspective, a.player_id)) {
+        let color = player_color(action.player_id).with_alpha(1.0 - action.timer.fraction());
+        match &action.command {
+            IssuedCommand::Unit { unit_tags, target, .. } => {
+                let end = match target {
+                    ActionTarget::Point(x, y) => {
+                        let end = world_position(*x, *y, map_size, tile_size);
+                        // Cross at the target point
+                        let d = tile_size * 0.4;
+                        gizmos.line_2d(end - Vec2::splat(d), end + Vec2::splat(d), color);
+                        gizmos.line_2d(end + Vec2::new(-d, d), end + Vec2::new(d, -d), color);
+                        Some(end)
+                    }
+                    ActionTarget::Unit(tag) => {
+                        let end = unit_position(*tag);
+                        if let Some(end) = end {
+                            gizmos.circle_2d(end, tile_size * 0.8, color);
+                        }
+                        end
+                    }
+                    ActionTarget::None => None,
+                };
+                for start in unit_tags.iter().filter_map(|t| unit_position(*t)) {
+                    match end {
+                        Some(end) => {
+                            gizmos.line_2d(start, end, color);
+                        }
+                        None => {
+                            gizmos.circle_2d(start, tile_size * 0.6, color);
+                        }
+                    }
+                }
+            }
+            IssuedCommand::Camera { x, y } => {
+                gizmos.rect_2d(world_position(*x, *y, map_size, tile_size), CAMERA_VIEW * tile_size, color);
+            }
+            IssuedCommand::Autocast { unit_tags, .. } => {
+                for pos in unit_tags.iter().filter_map(|t| unit_position(*t)) {
+                    gizmos.circle_2d(pos, tile_size * 0.7, color);
+                    gizmos.circle_2d(pos, tile_size * 0.9, color);
+                }
+            }
+        }
+    }
+}
+
+/// Window with the overlay switches, for bot commands and debug draws
+pub fn overlays_panel_system(
+    mut contexts: EguiContexts,
+    mut overlay: ResMut<BotActionOverlay>,
+    mut debug_draw: ResMut<DebugDrawOverlay>,
+) {
+    let Ok(ctx) = contexts.ctx_mut() else { return; };
+
+    egui::Window::new("Overlays").default_open(false).show(ctx, |ui| {
+        ui.checkbox(&mut overlay.enabled, "Bot commands");
+        ui.add(egui::Slider::new(&mut overlay.lifetime_secs, 0.2..=10.0).text("seconds shown"));
+        if let Some(latest) = overlay.actions.last() {
+            ui.label(format!("{} commands on screen, latest sent at loop {}", overlay.actions.len(), latest.game_loop));
+        }
+        ui.separator();
+        ui.checkbox(&mut debug_draw.enabled, "Bot debug draws");
+        for (player_id, batch) in &debug_draw.batches {
+            ui.label(format!(
+                "Player {}: {} texts, {} lines, {} boxes, {} spheres",
+                player_id, batch.get_text().len(), batch.get_lines().len(), batch.get_boxes().len(), batch.get_spheres().len()
+            ));
+        }
+    });
+}
@@ -0,0 +1,346 @@
+// src/bot_resources.rs
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use bevy_tokio_tasks::TokioTasksRuntime;
+use futures_util::FutureExt;
+use std::collections::HashMap;
+use std::fs;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use crate::app_settings::{AppSettings, ResourceLimitsConfig};
+use crate::bot_runner::{BotProcessStatus, BotRole};
+use crate::controller::ProxyResponseEvent;
+
+/// Kernel clock ticks per second used in /proc/<pid>/stat (USER_HZ)
+fn clock_ticks_per_sec() -> u64 {
+    static TICKS: OnceLock<u64> = OnceLock::new();
+    // SAFETY: sysconf only reads a system constant
+    *TICKS.get_or_init(|| u64::try_from(unsafe { libc::sysconf(libc::_SC_CLK_TCK) }).unwrap_or(0).max(1))
+}
+
+/// Size of the pages /proc/<pid>/stat counts RSS in
+fn page_size_bytes() -> u64 {
+    static PAGE_SIZE: OnceLock<u64> = OnceLock::new();
+    // SAFETY: sysconf only reads a system constant
+    *PAGE_SIZE.get_or_init(|| u64::try_from(unsafe { libc::sysconf(libc::_SC_PAGESIZE) }).unwrap_or(0).max(1))
+}
+
+/// Samples kept per bot; older ones are thinned out rather than dropped so long games stay visible
+const MAX_SAMPLES: usize = 4000;
+
+/// Resource usage of a bot's whole process tree at one point in time
+#[derive(Debug, Clone, Copy)]
+pub struct ResourceSample {
+    pub game_loop: u32,
+    /// User + system CPU time of all processes in the tree
+    pub cpu_secs: f64,
+    /// CPU use since the previous sample, 100% per fully used core
+    pub cpu_percent: f64,
+    pub rss_mb: f64,
+    pub threads: u64,
+}
+
+/// Samples and limit warnings of one bot
+#[derive(Debug, Default)]
+pub struct BotResourceHistory {
+    /// Process the samples belong to; a new PID (restart) starts a new history
+    pub pid: Option<u32>,
+    pub samples: Vec<ResourceSample>,
+    pub warnings: Vec<String>,
+    /// When the previous sample was taken, and the CPU ticks it counted
+    last_sample: Option<(Instant, u64)>,
+    rss_warned: bool,
+    cpu_warned: bool,
+    threads_warned: bool,
+}
+
+impl BotResourceHistory {
+    fn push(&mut self, sample: ResourceSample) {
+        self.samples.push(sample);
+        if self.samples.len() > MAX_SAMPLES {
+            // Keep every other sample of the older half
+            let half = self.samples.len() / 2;
+            let mut index = 0;
+            self.samples.retain(|_| {
+                index += 1;
+                index > half || index % 2 == 0
+            });
+        }
+    }
+}
+
+/// Resource holding the sampled usage of each bot
+#[derive(Resource, Default)]
+pub struct BotResourceMonitor {
+    pub player: BotResourceHistory,
+    pub opponent: BotResourceHistory,
+    /// Latest game loop observed by each player
+    game_loops: HashMap<u32, u32>,
+    next_sample: Option<Instant>,
+    /// /proc scan running on a blocking thread, so the frame doesn't wait for it
+    scan: Option<JoinHandle<Vec<TreeSample>>>,
+}
+
+impl BotResourceMonitor {
+    pub fn get(&self, role: BotRole) -> &BotResourceHistory {
+        match role {
+            BotRole::Player => &self.player,
+            BotRole::Opponent => &self.opponent,
+        }
+    }
+
+    fn get_mut(&mut self, role: BotRole) -> &mut BotResourceHistory {
+        match role {
+            BotRole::Player => &mut self.player,
+            BotRole::Opponent => &mut self.opponent,
+        }
+    }
+}
+
+/// Fields of /proc/<pid>/stat we care about
+struct ProcStat {
+    ppid: u32,
+    cpu_ticks: u64,
+    threads: u64,
+    rss_pages: u64,
+}
+
+fn read_proc_stat(pid: u32) -> Option<ProcStat> {
+    let content = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // The command name is in parentheses and may contain spaces, so split after the last ')'
+    let rest = &content[content.rfind(')')? + 1..];
+    let fields: Vec<&str> = rest.split_whitespace().collect();
+    // Indexes are the stat field numbers (1-based, see proc(5)) minus 3
+    Some(ProcStat {
+        ppid: fields.get(1)?.parse().ok()?,
+        cpu_ticks: fields.get(11)?.parse::<u64>().ok()? + fields.get(12)?.parse::<u64>().ok()?,
+        threads: fields.get(17)?.parse().ok()?,
+        rss_pages: fields.get(21)?.parse().ok()?,
+    })
+}
+
+/// Usage of one bot's process tree, summed over the root and all its descendants
+struct TreeSample {
+    role: BotRole,
+    pid: u32,
+    game_loop: u32,
+    taken_at: Instant,
+    cpu_ticks: u64,
+    rss_pages: u64,
+    threads: u64,
+}
+
+/// Read every process in /proc once and sum the tree of each `(role, root pid, game loop)`.
+/// Blocking: reads a file per process.
+fn sample_process_trees(roots: Vec<(BotRole, u32, u32)>) -> Vec<TreeSample> {
+    let mut stats: HashMap<u32, ProcStat> = HashMap::new();
+    let Ok(entries) = fs::read_dir("/proc") else { return Vec::new(); };
+    for entry in entries.filter_map(|e| e.ok()) {
+        let Some(pid) = entry.file_name().to_str().and_then(|name| name.parse::<u32>().ok()) else {
+            continue;
+        };
+        if let Some(stat) = read_proc_stat(pid) {
+            stats.insert(pid, stat);
+        }
+    }
+    let taken_at = Instant::now();
+
+    roots.into_iter().filter_map(|(role, pid, game_loop)| {
+        let (cpu_ticks, rss_pages, threads) = sum_process_tree(&stats, pid)?;
+        Some(TreeSample { role, pid, game_loop, taken_at, cpu_ticks, rss_pages, threads })
+    }).collect()
+}
+
+/// Sum CPU ticks, RSS pages and threads over `root` and all its descendants
+fn sum_process_tree(stats: &HashMap<u32, ProcStat>, root: u32) -> Option<(u64, u64, u64)> {
+    stats.get(&root)?;
+
+    let mut tree = vec![root];
+    let mut index = 0;
+    while index < tree.len() {
+        let parent = tree[index];
+        tree.extend(stats.iter().filter(|(_, s)| s.ppid == parent).map(|(pid, _)| *pid));
+        index += 1;
+    }
+
+    let totals = tree.iter().filter_map(|pid| stats.get(pid)).fold((0, 0, 0), |acc, s| {
+        (acc.0 + s.cpu_ticks, acc.1 + s.rss_pages, acc.2 + s.threads)
+    });
+    Some(totals)
+}
+
+/// System to sample each running bot's process tree at the configured interval and warn on limits.
+/// The /proc scan runs on a blocking thread; its result is picked up on a later frame.
+pub fn bot_resource_sampling_system(
+    mut responses: EventReader<ProxyResponseEvent>,
+    mut monitor: ResMut<BotResourceMonitor>,
+    bot_status: Res<BotProcessStatus>,
+    settings: Res<AppSettings>,
+    runtime: Res<TokioTasksRuntime>,
+) {
+    for event in responses.read() {
+        if event.response.has_observation() {
+            let game_loop = event.response.get_observation().get_observation().get_game_loop();
+            monitor.game_loops.insert(event.player_id, game_loop);
+        }
+    }
+
+    let limits = &settings.resource_limits;
+    if monitor.scan.as_ref().is_some_and(|scan| scan.is_finished()) {
+        if let Some(Ok(samples)) = monitor.scan.take().and_then(|scan| scan.now_or_never()) {
+            for tree in samples {
+                record_sample(&mut monitor, tree, limits);
+            }
+        }
+    }
+
+    let now = Instant::now();
+    if monitor.scan.is_some() || monitor.next_sample.is_some_and(|next| now < next) {
+        return;
+    }
+    monitor.next_sample = Some(now + Duration::from_millis(limits.sample_interval_ms.max(100)));
+
+    let mut roots = Vec::new();
+    for role in [BotRole::Player, BotRole::Opponent] {
+        let Some(pid) = bot_status.get(role).pid else { continue; };
+        let game_loop = monitor.game_loops.get(&role.player_id()).copied().unwrap_or(0);
+        let history = monitor.get_mut(role);
+        if history.pid != Some(pid) {
+            *history = BotResourceHistory { pid: Some(pid), ..default() };
+        }
+        roots.push((role, pid, game_loop));
+    }
+    if !roots.is_empty() {
+        monitor.scan = Some(runtime.runtime().spawn_blocking(move || sample_process_trees(roots)));
+    }
+}
+
+/// Add one scanned sample to its bot's history and warn once per limit and bot run
+fn record_sample(monitor: &mut BotResourceMonitor, tree: TreeSample, limits: &ResourceLimitsConfig) {
+    let history = monitor.get_mut(tree.role);
+    // The bot was restarted while the scan ran
+    if history.pid != Some(tree.pid) {
+        return;
+    }
+    let ticks_per_sec = clock_ticks_per_sec() as f64;
+    let cpu_percent = match history.last_sample {
+        Some((at, prev_ticks)) => {
+            let wall = tree.taken_at.duration_since(at).as_secs_f64();
+            // A child that exited takes its ticks out of the tree's total
+            let ticks = tree.cpu_ticks.saturating_sub(prev_ticks);
+            if wall > 0.0 { ticks as f64 / ticks_per_sec / wall * 100.0 } else { 0.0 }
+        }
+        None => 0.0,
+    };
+    history.last_sample = Some((tree.taken_at, tree.cpu_ticks));
+    let game_loop = tree.game_loop;
+    let sample = ResourceSample {
+        game_loop,
+        cpu_secs: tree.cpu_ticks as f64 / ticks_per_sec,
+        cpu_percent,
+        rss_mb: (tree.rss_pages * page_size_bytes()) as f64 / (1024.0 * 1024.0),
+        threads: tree.threads,
+    };
+    history.push(sample);
+
+    let role = tree.role;
+    check_limit(
+        role,
+        &mut history.warnings,
+        &mut history.rss_warned,
+        limits.max_rss_mb > 0.0 && sample.rss_mb > limits.max_rss_mb,
+        || format!("RSS {:.0} MB passed the {:.0} MB limit at loop {}", sample.rss_mb, limits.max_rss_mb, game_loop),
+    );
+    check_limit(
+        role,
+        &mut history.warnings,
+        &mut history.cpu_warned,
+        limits.max_cpu_percent > 0.0 && sample.cpu_percent > limits.max_cpu_percent,
+        || format!("CPU {:.0}% passed the {:.0}% limit at loop {}", sample.cpu_percent, limits.max_cpu_percent, game_loop),
+    );
+    check_limit(
+        role,
+        &mut history.warnings,
+        &mut history.threads_warned,
+        limits.max_threads > 0 && sample.threads > limits.max_threads,
+        || format!("{} threads passed the limit of {} at loop {}", sample.threads, limits.max_threads, game_loop),
+    );
+}
+
+fn check_limit(role: BotRole, warnings: &mut Vec<String>, warned: &mut bool, exceeded: bool, message: impl FnOnce() -> String) {
+    if exceeded && !*warned {
+        let message = message();
+        eprintln!("[bot_resources] {} bot: {}", role.label(), message);
+        warnings.push(message);
+        *warned = true;
+    }
+}
+
+/// Line chart of one metric against game loop, with an optional limit line
+fn plot(ui: &mut egui::Ui, label: &str, samples: &[ResourceSample], value: impl Fn(&ResourceSample) -> f64, limit: Option<f64>) {
+    let latest = samples.last().map(&value).unwrap_or(0.0);
+    ui.label(format!("{}: {:.1}", label, latest));
+
+    let (rect, _) = ui.allocate_exact_size(egui::vec2(ui.available_width(), 80.0), egui::Sense::hover());
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 2.0, egui::Color32::from_gray(20));
+    if samples.len() < 2 {
+        return;
+    }
+
+    let (first_loop, last_loop) = (samples[0].game_loop as f64, samples[samples.len() - 1].game_loop as f64);
+    let loop_span = (last_loop - first_loop).max(1.0);
+    let max = samples.iter().map(&value).fold(limit.unwrap_or(0.0), f64::max).max(1e-6) * 1.1;
+    let to_screen = |game_loop: f64, v: f64| {
+        egui::pos2(
+            rect.left() + ((game_loop - first_loop) / loop_span) as f32 * rect.width(),
+            rect.bottom() - (v / max) as f32 * rect.height(),
+        )
+    };
+
+    if let Some(limit) = limit {
+        let y = to_screen(first_loop, limit).y;
+        painter.hline(rect.x_range(), y, egui::Stroke::new(1.0, egui::Color32::from_rgb(220, 80, 80)));
+    }
+    let points: Vec<egui::Pos2> = samples.iter().map(|s| to_screen(s.game_loop as f64, value(s))).collect();
+    painter.add(egui::Shape::line(points, egui::Stroke::new(1.5, egui::Color32::from_rgb(100, 180, 255))));
+    painter.text(rect.left_top(), egui::Align2::LEFT_TOP, format!("{:.1}", max), egui::FontId::monospace(10.0), egui::Color32::GRAY);
+    painter.text(rect.right_bottom(), egui::Align2::RIGHT_BOTTOM, format!("loop {}", last_loop), egui::FontId::monospace(10.0), egui::Color32::GRAY);
+}
+
+/// Window plotting each bot's CPU, memory and threads against game loop
+pub fn bot_resources_panel_system(
+    mut contexts: EguiContexts,
+    monitor: Res<BotResourceMonitor>,
+    settings: Res<AppSettings>,
+) {
+    if monitor.player.samples.is_empty() && monitor.opponent.samples.is_empty() {
+        return;
+    }
+    let Ok(ctx) = contexts.ctx_mut() else { return; };
+    let limits = &settings.resource_limits;
+    let limit = |value: f64| (value > 0.0).then_some(value);
+
+    egui::Window::new("Bot resources").default_open(false).default_width(360.0).show(ctx, |ui| {
+        for role in [BotRole::Player, BotRole::Opponent] {
+            let history = monitor.get(role);
+            if history.samples.is_empty() {
+                continue;
+            }
+            egui::CollapsingHeader::new(format!("{} bot (pid {})", role.label(), history.pid.unwrap_or(0)))
+                .default_open(true)
+                .show(ui, |ui| {
+                    for warning in &history.warnings {
+                        ui.colored_label(egui::Color32::from_rgb(255, 170, 60), format!("⚠ {}", warning));
+                    }
+                    plot(ui, "RSS (MB)", &history.samples, |s| s.rss_mb, limit(limits.max_rss_mb));
+                    plot(ui, "CPU (%)", &history.samples, |s| s.cpu_percent, limit(limits.max_cpu_percent));
+                    plot(ui, "Threads", &history.samples, |s| s.threads as f64, limit(limits.max_threads as f64));
+                    if let Some(last) = history.samples.last() {
+                        ui.label(format!("Total CPU time: {:.1}s", last.cpu_secs));
+                    }
+                });
+        }
+    });
+}
@@ -1,24 +1,213 @@
 // src/bot_runner.rs
 use bevy::prelude::*;
 use bevy_tokio_tasks::TokioTasksRuntime;
-use std::process::Stdio;
+use std::collections::BTreeMap;
+use std::fs::OpenOptions;
+use std::io::{LineWriter, Write};
+use std::path::PathBuf;
+use std::os::unix::process::ExitStatusExt;
+use std::process::{ExitStatus, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tokio::process::Command;
 use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::sync::Notify;
+use crate::app_settings::{AppSettings, BotDefinition};
+use crate::controller::{ProxyGameEndedEvent, ProxyResponseEvent};
+
+/// Lines kept per bot console before the oldest are dropped
+const MAX_CONSOLE_LINES: usize = 20_000;
+
+/// Time a stopped bot gets to exit after SIGTERM before its process group is sent SIGKILL
+const STOP_GRACE_PERIOD: Duration = Duration::from_secs(3);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BotRole {
+    Player,
+    Opponent,
+}
+
+impl BotRole {
+    pub fn label(self) -> &'static str {
+        match self {
+            BotRole::Player => "player",
+            BotRole::Opponent => "opponent",
+        }
+    }
+
+    /// Player ID the server assigns to this bot: the player bot is the first entry of `player_setup`
+    pub fn player_id(self) -> u32 {
+        match self {
+            BotRole::Player => 1,
+            BotRole::Opponent => 2,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BotStream {
+    Stdout,
+    Stderr,
+}
+
+#[derive(Clone, Debug)]
+pub struct BotLogLine {
+    pub role: BotRole,
+    pub stream: BotStream,
+    pub text: String,
+}
+
+/// Lines read from bot processes, waiting to be moved into `BotProcessStatus` on the main thread
+#[derive(Resource, Clone, Default)]
+pub struct BotOutputBuffer(pub Arc<Mutex<Vec<BotLogLine>>>);
+
+/// State of one bot process
+#[derive(Default, Clone, Debug)]
+pub struct BotProcess {
+    pub running: bool,
+    pub command: Option<BotLaunch>,
+    pub log_dir: Option<PathBuf>,
+    /// PID of the bot (or its `bash -c` wrapper), which also leads the bot's process group
+    pub pid: Option<u32>,
+    pub started_at: Option<Instant>,
+    /// Set once the proxy forwarded a response for this bot's player
+    pub connected: bool,
+    pub output: Vec<BotLogLine>,
+    pub error: Option<String>,
+    /// How the last run ended, e.g. "exit code 0" or "signal 15"
+    pub exit: Option<String>,
+    /// Set when aiurgaze stopped the bot, so being killed isn't reported as a failure
+    pub stop_requested: bool,
+    /// Bumped on every start so a finished old run can't overwrite the state of a new one
+    generation: u64,
+    stop: Option<Arc<Notify>>,
+}
+
+impl BotProcess {
+    /// Ask the running process to stop; its task kills the process group
+    pub fn request_stop(&mut self) {
+        if let Some(stop) = &self.stop {
+            stop.notify_one();
+            self.stop_requested = true;
+        }
+    }
+}
 
 #[derive(Resource, Default, Clone, Debug)]
 pub struct BotProcessStatus {
-    pub player_bot_running: bool,
-    pub opponent_bot_running: bool,
-    pub player_bot_output: Vec<String>,
-    pub opponent_bot_output: Vec<String>,
-    pub player_bot_error: Option<String>,
-    pub opponent_bot_error: Option<String>,
+    pub player: BotProcess,
+    pub opponent: BotProcess,
+}
+
+impl BotProcessStatus {
+    pub fn get(&self, role: BotRole) -> &BotProcess {
+        match role {
+            BotRole::Player => &self.player,
+            BotRole::Opponent => &self.opponent,
+        }
+    }
+
+    pub fn get_mut(&mut self, role: BotRole) -> &mut BotProcess {
+        match role {
+            BotRole::Player => &mut self.player,
+            BotRole::Opponent => &mut self.opponent,
+        }
+    }
+
+    pub fn output(&self, role: BotRole) -> &[BotLogLine] {
+        &self.get(role).output
+    }
+
+    pub fn running(&self, role: BotRole) -> bool {
+        self.get(role).running
+    }
+
+    pub fn error(&self, role: BotRole) -> Option<&str> {
+        self.get(role).error.as_deref()
+    }
+}
+
+/// How a bot process is started
+#[derive(Clone, Debug)]
+pub enum BotLaunch {
+    /// Free-form command run through `bash -c`
+    Shell(String),
+    /// Executable from a `[[bots]]` entry, started directly with the proxy address already in `args`
+    Program {
+        executable: String,
+        args: Vec<String>,
+        working_dir: Option<String>,
+        env: BTreeMap<String, String>,
+    },
+}
+
+impl BotLaunch {
+    /// Launch a `[[bots]]` entry, pointing it at the proxy listening on `host:port`
+    pub fn from_definition(bot: &BotDefinition, host: &str, port: u16) -> Self {
+        BotLaunch::Program {
+            executable: bot.executable.clone(),
+            args: bot.resolved_args(host, port),
+            working_dir: bot.working_dir.clone(),
+            env: bot.env.clone(),
+        }
+    }
+
+    fn command(&self) -> Command {
+        match self {
+            BotLaunch::Shell(command) => {
+                let mut cmd = Command::new("bash");
+                cmd.arg("-c").arg(command);
+                cmd
+            }
+            BotLaunch::Program { executable, args, working_dir, env } => {
+                let mut cmd = Command::new(executable);
+                cmd.args(args).envs(env);
+                if let Some(dir) = working_dir {
+                    cmd.current_dir(dir);
+                }
+                cmd
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for BotLaunch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BotLaunch::Shell(command) => write!(f, "{}", command),
+            BotLaunch::Program { executable, args, working_dir, .. } => {
+                if let Some(dir) = working_dir {
+                    write!(f, "(in {}) ", dir)?;
+                }
+                write!(f, "{}", executable)?;
+                for arg in args {
+                    write!(f, " {}", arg)?;
+                }
+                Ok(())
+            }
+        }
+    }
 }
 
 #[derive(Event)]
 pub struct StartBotProcessesEvent {
-    pub player_bot_command: Option<String>,
-    pub opponent_bot_command: Option<String>,
+    pub player_bot: Option<BotLaunch>,
+    pub opponent_bot: Option<BotLaunch>,
+    /// Directory the bots' stdout/stderr are also written to, e.g. the match directory
+    pub log_dir: Option<PathBuf>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BotControlAction {
+    Stop,
+    Restart,
+}
+
+/// Request from the UI to stop or restart one bot
+#[derive(Event)]
+pub struct BotControlEvent {
+    pub role: BotRole,
+    pub action: BotControlAction,
 }
 
 /// System to handle starting bot processes when the event is triggered
@@ -26,142 +215,301 @@ pub fn bot_process_system(
     mut events: EventReader<StartBotProcessesEvent>,
     runtime: Res<TokioTasksRuntime>,
     mut bot_status: ResMut<BotProcessStatus>,
+    output_buffer: Res<BotOutputBuffer>,
 ) {
     for event in events.read() {
-        // Start player bot if command is provided
-        if let Some(cmd) = &event.player_bot_command {
-            if cmd.is_empty() {
+        let launches = [
+            (BotRole::Player, &event.player_bot),
+            (BotRole::Opponent, &event.opponent_bot),
+        ];
+        for (role, launch) in launches {
+            let Some(launch) = launch else {
                 continue;
+            };
+            start_bot(role, launch.clone(), event.log_dir.clone(), &runtime, &mut bot_status, &output_buffer);
+        }
+    }
+}
+
+/// Spawn one bot, replacing whatever state the previous run of that role left
+fn start_bot(
+    role: BotRole,
+    cmd: BotLaunch,
+    log_dir: Option<PathBuf>,
+    runtime: &TokioTasksRuntime,
+    bot_status: &mut BotProcessStatus,
+    output_buffer: &BotOutputBuffer,
+) {
+    println!("[bot_runner] Starting {} bot: {}", role.label(), cmd);
+    let process = bot_status.get_mut(role);
+    // A bot still running from an earlier start would fight the new one for the game slot
+    process.request_stop();
+
+    let stop = Arc::new(Notify::new());
+    let generation = process.generation + 1;
+    *process = BotProcess {
+        running: true,
+        command: Some(cmd.clone()),
+        log_dir: log_dir.clone(),
+        started_at: Some(Instant::now()),
+        generation,
+        stop: Some(stop.clone()),
+        ..default()
+    };
+
+    let buffer = output_buffer.clone();
+    runtime.spawn_background_task(move |mut ctx| async move {
+        let result = run_bot_command(&cmd, role, generation, log_dir, buffer, stop, ctx.clone()).await;
+
+        ctx.run_on_main_thread(move |world| {
+            let Some(mut status) = world.world.get_resource_mut::<BotProcessStatus>() else {
+                return;
+            };
+            let process = status.get_mut(role);
+            if process.generation != generation {
+                return;
             }
-            
-            println!("[bot_runner] Starting player bot: {}", cmd);
-            bot_status.player_bot_running = true;
-            bot_status.player_bot_error = None;
-            bot_status.player_bot_output.clear();
-
-            let cmd_clone = cmd.clone();
-            runtime.spawn_background_task(|mut ctx| async move {
-                let result = run_bot_command(&cmd_clone, true).await;
-
-                ctx.run_on_main_thread(move |world| {
-                    let Some(mut status) = world.world.get_resource_mut::<BotProcessStatus>() else {
-                        return;
-                    };
-                    
-                    status.player_bot_running = false;
-                    match result {
-                        Ok(output) => {
-                            println!("[bot_runner] Player bot completed successfully");
-                            status.player_bot_output = output;
-                        }
-                        Err(e) => {
-                            eprintln!("[bot_runner] Player bot failed: {}", e);
-                            status.player_bot_error = Some(e);
-                        }
+
+            process.running = false;
+            process.pid = None;
+            process.stop = None;
+            match result {
+                Ok(exit_status) => {
+                    let exit = describe_exit(exit_status);
+                    if exit_status.success() {
+                        println!("[bot_runner] {} bot completed successfully", role.label());
+                    } else if process.stop_requested {
+                        println!("[bot_runner] {} bot stopped: {}", role.label(), exit);
+                    } else {
+                        eprintln!("[bot_runner] {} bot failed: {}", role.label(), exit);
+                        process.error = Some(format!("{} bot process exited with {}", role.label(), exit));
                     }
-                }).await;
-            });
+                    process.exit = Some(exit);
+                }
+                Err(e) => {
+                    eprintln!("[bot_runner] {} bot failed: {}", role.label(), e);
+                    process.error = Some(e);
+                }
+            }
+        }).await;
+    });
+}
+
+/// System to apply Stop/Restart requests from the UI
+pub fn bot_control_system(
+    mut events: EventReader<BotControlEvent>,
+    runtime: Res<TokioTasksRuntime>,
+    mut bot_status: ResMut<BotProcessStatus>,
+    output_buffer: Res<BotOutputBuffer>,
+) {
+    for event in events.read() {
+        match event.action {
+            BotControlAction::Stop => {
+                println!("[bot_runner] Stopping {} bot", event.role.label());
+                bot_status.get_mut(event.role).request_stop();
+            }
+            BotControlAction::Restart => {
+                let process = bot_status.get(event.role);
+                let Some(cmd) = process.command.clone() else {
+                    continue;
+                };
+                let log_dir = process.log_dir.clone();
+                start_bot(event.role, cmd, log_dir, &runtime, &mut bot_status, &output_buffer);
+            }
         }
+    }
+}
 
-        // Start opponent bot if command is provided
-        if let Some(cmd) = &event.opponent_bot_command {
-            if cmd.is_empty() {
-                continue;
+/// System to enforce the startup timeout and maximum run time, and stop bots when the game ends
+pub fn bot_lifecycle_system(
+    mut responses: EventReader<ProxyResponseEvent>,
+    mut game_ended: EventReader<ProxyGameEndedEvent>,
+    mut bot_status: ResMut<BotProcessStatus>,
+    settings: Res<AppSettings>,
+) {
+    for event in responses.read() {
+        for role in [BotRole::Player, BotRole::Opponent] {
+            let process = bot_status.get_mut(role);
+            if process.running && role.player_id() == event.player_id {
+                process.connected = true;
             }
-            
-            println!("[bot_runner] Starting opponent bot: {}", cmd);
-            bot_status.opponent_bot_running = true;
-            bot_status.opponent_bot_error = None;
-            bot_status.opponent_bot_output.clear();
-
-            let cmd_clone = cmd.clone();
-            runtime.spawn_background_task(|mut ctx| async move {
-                let result = run_bot_command(&cmd_clone, false).await;
-
-                ctx.run_on_main_thread(move |world| {
-                    let Some(mut status) = world.world.get_resource_mut::<BotProcessStatus>() else {
-                        return;
-                    };
-                    
-                    status.opponent_bot_running = false;
-                    match result {
-                        Ok(output) => {
-                            println!("[bot_runner] Opponent bot completed successfully");
-                            status.opponent_bot_output = output;
-                        }
-                        Err(e) => {
-                            eprintln!("[bot_runner] Opponent bot failed: {}", e);
-                            status.opponent_bot_error = Some(e);
-                        }
-                    }
-                }).await;
-            });
+        }
+    }
+
+    let game_ended = game_ended.read().last().is_some();
+    let limits = &settings.bot_runner;
+    for role in [BotRole::Player, BotRole::Opponent] {
+        let process = bot_status.get_mut(role);
+        if !process.running || process.stop.is_none() {
+            continue;
+        }
+        let elapsed = process.started_at.map(|t| t.elapsed()).unwrap_or_default();
+
+        let reason = if game_ended && limits.kill_on_game_end {
+            Some("game ended".to_string())
+        } else if !process.connected && limits.startup_timeout_secs > 0 && elapsed.as_secs() >= limits.startup_timeout_secs {
+            Some(format!("did not connect within {}s", limits.startup_timeout_secs))
+        } else if limits.max_game_secs > 0 && elapsed.as_secs() >= limits.max_game_secs {
+            Some(format!("exceeded the maximum game duration of {}s", limits.max_game_secs))
+        } else {
+            None
+        };
+        let Some(reason) = reason else { continue; };
+
+        println!("[bot_runner] Stopping {} bot: {}", role.label(), reason);
+        process.request_stop();
+        // Only ask once; the task clears `running` when the process is gone
+        process.stop = None;
+        if !game_ended {
+            process.error = Some(format!("{} bot {}", role.label(), reason));
+        }
+    }
+}
+
+/// System to kill every bot process group when the app exits, so bots don't outlive the window
+pub fn kill_bots_on_exit_system(mut exit: EventReader<AppExit>, bot_status: Res<BotProcessStatus>) {
+    if exit.read().last().is_none() {
+        return;
+    }
+    for role in [BotRole::Player, BotRole::Opponent] {
+        if let Some(pid) = bot_status.get(role).pid {
+            println!("[bot_runner] Killing {} bot process group {}", role.label(), pid);
+            kill_process_group(pid, libc::SIGKILL);
+        }
+    }
+}
+
+/// Send `signal` (e.g. `libc::SIGTERM`) to the process group led by `pid`.
+/// A group that is already gone is not an error.
+fn kill_process_group(pid: u32, signal: libc::c_int) {
+    let Ok(pgid) = libc::pid_t::try_from(pid) else {
+        eprintln!("[bot_runner] Invalid process group {}", pid);
+        return;
+    };
+    // SAFETY: killpg only sends a signal; it touches no memory of ours
+    if unsafe { libc::killpg(pgid, signal) } == -1 {
+        let e = std::io::Error::last_os_error();
+        if e.raw_os_error() != Some(libc::ESRCH) {
+            eprintln!("[bot_runner] Failed to send signal {} to process group {}: {}", signal, pid, e);
+        }
+    }
+}
+
+fn describe_exit(status: ExitStatus) -> String {
+    match (status.code(), status.signal()) {
+        (Some(code), _) => format!("exit code {}", code),
+        (None, Some(signal)) => format!("signal {}", signal),
+        _ => "unknown status".to_string(),
+    }
+}
+
+/// System to move bot output read by the background tasks into `BotProcessStatus`
+pub fn bot_output_system(output_buffer: Res<BotOutputBuffer>, mut bot_status: ResMut<BotProcessStatus>) {
+    let lines = std::mem::take(&mut *output_buffer.0.lock().unwrap());
+    if lines.is_empty() {
+        return;
+    }
+    for line in lines {
+        bot_status.get_mut(line.role).output.push(line);
+    }
+    for role in [BotRole::Player, BotRole::Opponent] {
+        let output = &mut bot_status.get_mut(role).output;
+        if output.len() > MAX_CONSOLE_LINES {
+            let excess = output.len() - MAX_CONSOLE_LINES;
+            output.drain(..excess);
         }
     }
 }
 
-/// Run a bash command asynchronously and capture output
-async fn run_bot_command(command: &str, is_player: bool) -> Result<Vec<String>, String> {
-    let bot_type = if is_player { "player" } else { "opponent" };
+/// Read lines from a bot pipe as they arrive, hand them to the main thread and append them to `log_file`
+async fn forward_lines<R>(reader: R, role: BotRole, stream: BotStream, buffer: BotOutputBuffer, log_file: Option<PathBuf>)
+where
+    R: tokio::io::AsyncRead + Unpin,
+{
+    let mut log = log_file.and_then(|path| {
+        OpenOptions::new().create(true).append(true).open(&path)
+            .map_err(|e| eprintln!("[bot_runner] Failed to open {}: {}", path.display(), e))
+            .ok()
+            .map(LineWriter::new)
+    });
+    let mut lines = BufReader::new(reader).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        match stream {
+            BotStream::Stdout => println!("[bot_runner:{}:stdout] {}", role.label(), line),
+            BotStream::Stderr => eprintln!("[bot_runner:{}:stderr] {}", role.label(), line),
+        }
+        if let Some(file) = log.as_mut() {
+            if writeln!(file, "{}", line).is_err() {
+                log = None;
+            }
+        }
+        buffer.0.lock().unwrap().push(BotLogLine { role, stream, text: line });
+    }
+}
+
+/// Run a bot in its own process group, streaming its output into `buffer` until it exits or `stop` fires
+async fn run_bot_command(
+    command: &BotLaunch,
+    role: BotRole,
+    generation: u64,
+    log_dir: Option<PathBuf>,
+    buffer: BotOutputBuffer,
+    stop: Arc<Notify>,
+    mut ctx: bevy_tokio_tasks::TaskContext,
+) -> Result<ExitStatus, String> {
+    let bot_type = role.label();
     println!("[bot_runner] Executing {} bot command: {}", bot_type, command);
 
-    let mut child = Command::new("bash")
-        .arg("-c")
-        .arg(command)
+    let mut child = command.command()
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
+        .process_group(0)
+        .kill_on_drop(true)
         .spawn()
         .map_err(|e| format!("Failed to spawn {} bot process: {}", bot_type, e))?;
 
+    let pid = child.id()
+        .ok_or_else(|| format!("{} bot process exited before it could be tracked", bot_type))?;
+    ctx.run_on_main_thread(move |world| {
+        if let Some(mut status) = world.world.get_resource_mut::<BotProcessStatus>() {
+            let process = status.get_mut(role);
+            if process.generation == generation {
+                process.pid = Some(pid);
+            }
+        }
+    }).await;
+
     let stdout = child.stdout.take()
         .ok_or_else(|| format!("Failed to capture stdout for {} bot", bot_type))?;
     let stderr = child.stderr.take()
         .ok_or_else(|| format!("Failed to capture stderr for {} bot", bot_type))?;
 
-    let mut output_lines = Vec::new();
-
-    // Spawn task to read stdout
-    let stdout_handle = tokio::spawn(async move {
-        let reader = BufReader::new(stdout);
-        let mut lines = reader.lines();
-        let mut collected = Vec::new();
-        while let Ok(Some(line)) = lines.next_line().await {
-            println!("[bot_runner:{}:stdout] {}", bot_type, line);
-            collected.push(line);
-        }
-        collected
-    });
+    let log_file = |stream: &str| log_dir.as_ref().map(|dir| dir.join(format!("{}.{}.log", bot_type, stream)));
+    let stdout_handle = tokio::spawn(forward_lines(stdout, role, BotStream::Stdout, buffer.clone(), log_file("stdout")));
+    let stderr_handle = tokio::spawn(forward_lines(stderr, role, BotStream::Stderr, buffer, log_file("stderr")));
 
-    // Spawn task to read stderr
-    let stderr_handle = tokio::spawn(async move {
-        let reader = BufReader::new(stderr);
-        let mut lines = reader.lines();
-        let mut collected = Vec::new();
-        while let Ok(Some(line)) = lines.next_line().await {
-            eprintln!("[bot_runner:{}:stderr] {}", bot_type, line);
-            collected.push(line);
+    // Wait for the process to complete, or kill its whole group when asked to stop
+    let status = tokio::select! {
+        status = child.wait() => status,
+        _ = stop.notified() => {
+            kill_process_group(pid, libc::SIGTERM);
+            match tokio::time::timeout(STOP_GRACE_PERIOD, child.wait()).await {
+                Ok(status) => status,
+                Err(_) => {
+                    kill_process_group(pid, libc::SIGKILL);
+                    child.wait().await
+                }
+            }
         }
-        collected
-    });
-
-    // Wait for process to complete
-    let status = child.wait().await
-        .map_err(|e| format!("Failed to wait for {} bot process: {}", bot_type, e))?;
+    }
+    .map_err(|e| format!("Failed to wait for {} bot process: {}", bot_type, e))?;
 
-    // Collect output
-    let stdout_lines = stdout_handle.await
+    // Let the readers drain what is left in the pipes
+    stdout_handle.await
         .map_err(|e| format!("Failed to join stdout task: {}", e))?;
-    let stderr_lines = stderr_handle.await
+    stderr_handle.await
         .map_err(|e| format!("Failed to join stderr task: {}", e))?;
 
-    output_lines.extend(stdout_lines);
-    output_lines.extend(stderr_lines);
-
-    if !status.success() {
-        return Err(format!("{} bot process exited with status: {:?}", bot_type, status));
-    }
-    
     println!("[bot_runner] {} bot process completed with status: {:?}", bot_type, status);
-    Ok(output_lines)
+    Ok(status)
 }
-
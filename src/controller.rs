@@ -1,20 +1,79 @@
 use bevy::asset::AssetServer;
-use sc2_proto::sc2api::{Response, Response_oneof_response::{game_info, observation}};
-use bevy::prelude::{Commands, Res, ResMut, Resource, Query, Event, EventReader};
+use sc2_proto::sc2api::{Request, Response, ResponseObservation, Response_oneof_response::{create_game, game_info, join_game, observation}};
+use bevy::prelude::{Commands, Entity, Res, ResMut, Resource, Query, Event, EventReader, With};
+use std::collections::{BTreeSet, HashMap};
+use std::net::SocketAddr;
 use bevy_ecs_tilemap::prelude::{TileColor, TileStorage};
 use bevy_ecs_tilemap::tiles::TilePos;
 use bevy_tokio_tasks::TokioTasksRuntime;
-use crate::proxy_ws::ProxyWS;
+use crate::proxy_ws::{ActionOutcome, ProxyWS};
 use crate::map::{spawn_tilemap, TerrainLayers, TerrainLayer, blend_tile_color};
 use crate::entity_system::EntitySystem;
-use crate::units::{handle_observation, UnitBuildProgress, UnitRegistry, ObservationUnitTags};
+use crate::units::{handle_observation, merge_observations, UnitBuildProgress, UnitRegistry, ObservationUnitTags, SelectedUnit};
 use crate::app_settings::AppSettings;
+use crate::matches::MatchRecorder;
+use crate::inspector::ProtocolMessageBuffer;
+use crate::debug_commands::DebugCommandQueue;
+use crate::latency::LatencyBuffer;
+use crate::step_control::StepControl;
 use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
 
-// Event for proxy responses
+// Event for proxy responses, tagged with the player whose client received them
 #[derive(Event)]
-pub struct ProxyResponseEvent(pub Response);
+pub struct ProxyResponseEvent {
+    pub player_id: u32,
+    pub response: Response,
+}
+
+/// Event for requests a bot sent through the proxy, tagged with its player
+#[derive(Event)]
+pub struct ProxyRequestEvent {
+    pub player_id: u32,
+    pub request: Request,
+}
+
+/// Event for an action request a bot sent, paired with the response that answered it
+#[derive(Event)]
+pub struct ProxyActionResultEvent(pub ActionOutcome);
+
+/// Resource tracking which players' streams have been seen and whose observations are drawn
+#[derive(Resource, Default)]
+pub struct PlayerPerspective {
+    /// Player whose observations drive the game screen; the first player seen by default.
+    /// In the merged view this is the viewpoint alliances are remapped to.
+    pub active: Option<u32>,
+    /// Draw the units of all players' streams combined
+    pub merged: bool,
+    pub known_players: BTreeSet<u32>,
+    /// Latest observation received for each player
+    pub latest_observations: HashMap<u32, ResponseObservation>,
+}
+
+/// Whether the perspective shows what `player_id` does; overlays filter by player with it
+pub(crate) fn visible_for(perspective: &PlayerPerspective, player_id: u32) -> bool {
+    perspective.merged || perspective.active.is_none_or(|active| active == player_id)
+}
+
+/// Event sent when every bot client of a game has disconnected from the proxy
+#[derive(Event)]
+pub struct ProxyGameEndedEvent;
+
+/// Proxy callback, queued for the main thread in the order it fired
+enum ProxyNotice {
+    Response(u32, Response),
+    Request(u32, Request),
+    ActionResult(ActionOutcome),
+    GameEnded,
+}
+
+/// Address the proxy listener actually bound, once it is listening
+#[derive(Resource, Default)]
+pub struct ProxyListenAddr(pub Option<SocketAddr>);
+
+/// Resource telling whether the proxy task is alive, so it can be restarted if it fails
+#[derive(Resource, Default)]
+pub struct ProxyRunning(pub bool);
 
 // Resource to store static terrain layers and tile storage
 #[derive(Resource)]
@@ -25,38 +84,154 @@ pub struct MapResource {
     pub last_energy_hash: u64,
 }
 
-pub fn setup_proxy(runtime: Res<TokioTasksRuntime>, settings: Res<AppSettings>) {
+pub fn setup_proxy(
+    runtime: Res<TokioTasksRuntime>,
+    settings: Res<AppSettings>,
+    matches: Res<MatchRecorder>,
+    latency: Res<LatencyBuffer>,
+    messages: Res<ProtocolMessageBuffer>,
+    step_control: Res<StepControl>,
+    debug_queue: Res<DebugCommandQueue>,
+) {
     println!("======setup_proxy====");
 
     let listen_addr = format!("{}:{}", settings.starcraft.listen_url, settings.starcraft.listen_port);
     let upstream_addr = format!("{}:{}/sc2api", settings.starcraft.upstream_url, settings.starcraft.upstream_port);
+    let upstream_urls = settings.starcraft.upstream_urls();
+    // Sessions are traced into the directory of the match in progress
+    let trace_dir = matches.trace_dir_handle();
+    let latency_buffer = latency.clone();
+    let message_buffer = settings.inspector.enabled.then(|| messages.clone());
+    let step_gate = step_control.0.clone();
+    let debug_queue = debug_queue.0.clone();
 
     // Create proxy with callback that emits Bevy events directly
     runtime.spawn_background_task(|ctx| async move {
-        let proxy = ProxyWS::new(
+        let mut exit_ctx = ctx.clone();
+        let listen_ctx = ctx.clone();
+
+        // Responses, requests and the game end go through one channel, so the main thread sees
+        // them in the order they happened (e.g. the final observation before the game end)
+        let (response_tx, mut notices) = tokio::sync::mpsc::unbounded_channel();
+        let request_tx = response_tx.clone();
+        let action_tx = response_tx.clone();
+        let end_tx = response_tx.clone();
+        let mut forward_ctx = ctx.clone();
+        tokio::spawn(async move {
+            while let Some(first) = notices.recv().await {
+                let mut batch = vec![first];
+                while let Ok(next) = notices.try_recv() {
+                    batch.push(next);
+                }
+                forward_ctx.run_on_main_thread(move |ctx| {
+                    for notice in batch {
+                        match notice {
+                            ProxyNotice::Response(player_id, response) => {
+                                ctx.world.send_event(ProxyResponseEvent { player_id, response });
+                            }
+                            ProxyNotice::Request(player_id, request) => {
+                                ctx.world.send_event(ProxyRequestEvent { player_id, request });
+                            }
+                            ProxyNotice::ActionResult(outcome) => {
+                                ctx.world.send_event(ProxyActionResultEvent(outcome));
+                            }
+                            ProxyNotice::GameEnded => {
+                                ctx.world.send_event(ProxyGameEndedEvent);
+                            }
+                        }
+                    }
+                }).await;
+            }
+        });
+
+        let mut proxy = ProxyWS::new(
             &listen_addr,
             &upstream_addr,
-            move |resp| {
-                // This callback runs in the async task, so we need to queue the event
-                // to be sent on the main thread
-                let mut ctx_clone = ctx.clone();
-                tokio::spawn(async move {
-                    ctx_clone.run_on_main_thread(move |ctx| {
-                        ctx.world.send_event(ProxyResponseEvent(resp));
-                    }).await;
-                });
+            move |player_id, resp| {
+                let _ = response_tx.send(ProxyNotice::Response(player_id, resp));
             }
-        );
+        )
+        .with_upstreams(upstream_urls)
+        .with_step_gate(step_gate)
+        .with_debug_queue(debug_queue)
+        .with_shared_trace_dir(trace_dir)
+        .with_on_request(move |player_id, request| {
+            let _ = request_tx.send(ProxyNotice::Request(player_id, request));
+        })
+        .with_on_action_result(move |outcome| {
+            let _ = action_tx.send(ProxyNotice::ActionResult(outcome));
+        })
+        .with_on_latency(move |sample| latency_buffer.0.lock().unwrap().push(sample))
+        .with_on_listening(move |addr| {
+            let mut ctx_clone = listen_ctx.clone();
+            tokio::spawn(async move {
+                ctx_clone.run_on_main_thread(move |ctx| {
+                    ctx.world.insert_resource(ProxyListenAddr(Some(addr)));
+                }).await;
+            });
+        })
+        .with_on_game_end(move || {
+            let _ = end_tx.send(ProxyNotice::GameEnded);
+        });
+        if let Some(buffer) = message_buffer {
+            proxy = proxy.with_on_message(move |message| buffer.0.lock().unwrap().push(message));
+        }
 
         if let Err(e) = proxy.run().await {
             eprintln!("Proxy task failed: {e}");
         }
+        exit_ctx.run_on_main_thread(|ctx| {
+            ctx.world.insert_resource(ProxyRunning(false));
+        }).await;
     });
 
     println!("======Proxy task spawned====");
 }
 
 
+/// Forget everything drawn for the previous game so the next one starts from a clean slate
+fn reset_game_state(
+    commands: &mut Commands,
+    registry: &mut UnitRegistry,
+    seen_tags: &mut ObservationUnitTags,
+    selected: &mut SelectedUnit,
+    perspective: &mut PlayerPerspective,
+    map_res: Option<&MapResource>,
+    tilemap_query: &Query<Entity, With<TileStorage>>,
+) {
+    println!("[controller] Resetting game state for a new game");
+    reset_units(commands, registry, seen_tags, selected, perspective);
+    // Player IDs of the previous game mean nothing in the next one
+    perspective.active = None;
+    perspective.known_players.clear();
+
+    if let Some(map_res) = map_res {
+        for tile in map_res.tile_storage.iter().flatten() {
+            commands.entity(*tile).despawn();
+        }
+    }
+    for tilemap in tilemap_query.iter() {
+        commands.entity(tilemap).despawn();
+    }
+    commands.remove_resource::<MapResource>();
+}
+
+/// Forget the units drawn so far but keep the map, for a game that restarted on the same map
+fn reset_units(
+    commands: &mut Commands,
+    registry: &mut UnitRegistry,
+    seen_tags: &mut ObservationUnitTags,
+    selected: &mut SelectedUnit,
+    perspective: &mut PlayerPerspective,
+) {
+    for (_, entity) in registry.map.drain() {
+        commands.entity(entity).despawn();
+    }
+    seen_tags.seen_tags.clear();
+    selected.tag = None;
+    perspective.latest_observations.clear();
+}
+
 fn calculate_layer_hash(layer: &Option<TerrainLayer>) -> u64 {
     let mut hasher = DefaultHasher::new();
     if let Some(layer) = layer {
@@ -115,10 +290,41 @@ pub fn response_controller_system(
     mut tile_color_query: Query<&mut TileColor>,
     unit_query: Query<&UnitBuildProgress>,
     mut seen_tags: ResMut<ObservationUnitTags>,
+    mut perspective: ResMut<PlayerPerspective>,
+    mut selected: ResMut<SelectedUnit>,
+    tilemap_query: Query<Entity, With<TileStorage>>,
 ) {
+    // MapResource is inserted through commands, so `map_res` stays empty until the next frame
+    let mut map_spawned = false;
     for event in events.read() {
-        match event.0.response.as_ref().unwrap() {
+        if perspective.known_players.insert(event.player_id) {
+            println!("[controller] Receiving responses for player {}", event.player_id);
+        }
+        let active = *perspective.active.get_or_insert(event.player_id);
+
+        let Some(response) = event.response.response.as_ref() else {
+            continue;
+        };
+        match response {
             observation (obs)  => {
+                // The game loop going backwards means the game restarted: its units are stale
+                let game_loop = obs.get_observation().get_game_loop();
+                let previous = perspective.latest_observations.get(&event.player_id)
+                    .map(|prev| prev.get_observation().get_game_loop());
+                if previous.is_some_and(|previous| game_loop < previous) {
+                    println!("[controller] Game loop went back from {} to {}, clearing units", previous.unwrap_or(0), game_loop);
+                    reset_units(&mut commands, &mut registry, &mut seen_tags, &mut selected, &mut perspective);
+                }
+                perspective.latest_observations.insert(event.player_id, obs.clone());
+                // Only the selected player's stream is drawn, unless all streams are merged
+                let obs = if perspective.merged {
+                    merge_observations(active, &perspective.latest_observations)
+                } else if event.player_id == active {
+                    obs.clone()
+                } else {
+                    continue;
+                };
+
                 // Update dynamic layers (creep, energy, visibility) only if changed
                 if let Some(ref mut map_res) = map_res {
                     let obs_data = obs.observation.as_ref().unwrap();
@@ -154,22 +360,45 @@ pub fn response_controller_system(
                     }
                 }
 
+                // Units can't be placed before game_info has created the map
+                let Some(map_size) = map_res.as_ref().map(|m| {
+                    let (w, h) = m.static_layers.get_dimensions();
+                    (w as f32, h as f32)
+                }) else {
+                    continue;
+                };
+
                 handle_observation(
                     &mut commands,
                     &asset_server,
                     &mut registry,
                     &entity_system,
                     &obs,
-                    unit_query,
+                    &unit_query,
                     &mut seen_tags,
-                    map_res.as_ref().map(|m| {
-                        let (w, h) = m.static_layers.get_dimensions();
-                        (w as f32, h as f32)
-                    }).unwrap(),
+                    map_size,
                 );
 
             }
-            game_info (gi) =>  {
+            create_game (_) => {
+                reset_game_state(
+                    &mut commands, &mut registry, &mut seen_tags, &mut selected,
+                    &mut perspective, map_res.as_deref(), &tilemap_query,
+                );
+                map_res = None;
+            }
+            // Every player of a game joins before anyone observes it, so a join after
+            // observations means a new game (e.g. against a computer, created elsewhere)
+            join_game (_) if !perspective.latest_observations.is_empty() => {
+                reset_game_state(
+                    &mut commands, &mut registry, &mut seen_tags, &mut selected,
+                    &mut perspective, map_res.as_deref(), &tilemap_query,
+                );
+                map_res = None;
+            }
+            // Bots may ask for game_info again mid-game; the map is only built once per game
+            game_info (gi) if event.player_id == active && map_res.is_none() && !map_spawned =>  {
+
                 let start_raw = gi.start_raw.as_ref().unwrap();
                 let start_pos = start_raw.start_locations.get(0).unwrap();
 
@@ -201,6 +430,7 @@ pub fn response_controller_system(
                     last_creep_hash: 0,
                     last_energy_hash: 0,
                 });
+                map_spawned = true;
 
                 println!("Spawned tilemap, start pos: {:?}", start_pos);
             }
@@ -0,0 +1,262 @@
+// src/debug_commands.rs
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use protobuf::RepeatedField;
+use sc2_proto::common::Point2D;
+use sc2_proto::debug::{DebugCommand, DebugCreateUnit, DebugGameState, DebugKillUnit, DebugSetUnitValue, DebugSetUnitValue_UnitValue};
+use sc2_proto::sc2api::Request;
+use std::sync::{Arc, Mutex};
+use crate::controller::{MapResource, PlayerPerspective};
+use crate::entity_system::EntitySystem;
+use crate::units::{map_position, SelectedUnit};
+
+/// Unit types listed at once in the picker
+const MAX_PICKER_ROWS: usize = 200;
+
+/// A debug command waiting for the proxy to inject it
+#[derive(Debug, Clone)]
+pub struct PendingDebugCommand {
+    /// Connection to send it through; cheats like reveal map apply to the player sending them.
+    /// `None` lets whichever client requests first carry it.
+    pub player_id: Option<u32>,
+    pub command: DebugCommand,
+}
+
+pub type DebugQueue = Arc<Mutex<Vec<PendingDebugCommand>>>;
+
+/// Remove the commands `player_id`'s connection should carry and wrap them in one RequestDebug
+pub fn take_debug_commands(queue: &DebugQueue, player_id: u32) -> Option<Request> {
+    let mut queue = queue.lock().unwrap();
+    if queue.is_empty() {
+        return None;
+    }
+    let (mine, rest): (Vec<_>, Vec<_>) = queue.drain(..).partition(|c| c.player_id.is_none_or(|p| p == player_id));
+    *queue = rest;
+    if mine.is_empty() {
+        return None;
+    }
+    let mut request = Request::new();
+    request.mut_debug().set_debug(RepeatedField::from_vec(mine.into_iter().map(|c| c.command).collect()));
+    Some(request)
+}
+
+/// Resource sharing the debug queue with the proxy
+#[derive(Resource, Clone, Default)]
+pub struct DebugCommandQueue(pub DebugQueue);
+
+impl DebugCommandQueue {
+    pub fn push(&self, player_id: Option<u32>, command: DebugCommand) {
+        self.0.lock().unwrap().push(PendingDebugCommand { player_id, command });
+    }
+
+    pub fn pending(&self) -> usize {
+        self.0.lock().unwrap().len()
+    }
+}
+
+pub fn create_unit(unit_type: u32, owner: u32, x: f32, y: f32, quantity: u32) -> DebugCommand {
+    let mut pos = Point2D::new();
+    pos.set_x(x);
+    pos.set_y(y);
+    let mut create = DebugCreateUnit::new();
+    create.set_unit_type(unit_type);
+    create.set_owner(owner as i32);
+    create.set_pos(pos);
+    create.set_quantity(quantity.max(1));
+    let mut command = DebugCommand::new();
+    command.set_create_unit(create);
+    command
+}
+
+pub fn kill_units(tags: Vec<u64>) -> DebugCommand {
+    let mut kill = DebugKillUnit::new();
+    kill.set_tag(tags);
+    let mut command = DebugCommand::new();
+    command.set_kill_unit(kill);
+    command
+}
+
+pub fn set_unit_value(tag: u64, unit_value: DebugSetUnitValue_UnitValue, value: f32) -> DebugCommand {
+    let mut set = DebugSetUnitValue::new();
+    set.set_unit_tag(tag);
+    set.set_unit_value(unit_value);
+    set.set_value(value);
+    let mut command = DebugCommand::new();
+    command.set_unit_value(set);
+    command
+}
+
+/// Cheats toggle on and off each time they are sent
+pub fn game_state(state: DebugGameState) -> DebugCommand {
+    let mut command = DebugCommand::new();
+    command.set_game_state(state);
+    command
+}
+
+/// State of the debug window between frames
+#[derive(Resource)]
+pub struct DebugPanelState {
+    pub unit_type: Option<u32>,
+    pub unit_filter: String,
+    pub owner: u32,
+    pub quantity: u32,
+    /// Right-clicking the map creates the picked unit there
+    pub placing: bool,
+    pub value: f32,
+    /// Player whose connection carries the commands
+    pub target_player: Option<u32>,
+}
+
+impl Default for DebugPanelState {
+    fn default() -> Self {
+        Self {
+            unit_type: None,
+            unit_filter: String::new(),
+            owner: 1,
+            quantity: 1,
+            placing: false,
+            value: 100.0,
+            target_player: None,
+        }
+    }
+}
+
+/// Map coordinates under the mouse cursor, if it is over the map
+fn cursor_map_position(
+    windows: &Query<&Window>,
+    camera_query: &Query<(&Camera, &GlobalTransform)>,
+    map_res: &MapResource,
+    tile_size: f32,
+) -> Option<Vec2> {
+    let window = windows.single().ok()?;
+    let (camera, camera_transform) = camera_query.single().ok()?;
+    let ray = camera.viewport_to_world(camera_transform, window.cursor_position()?).ok()?;
+    let (w, h) = map_res.static_layers.get_dimensions();
+    let pos = map_position(ray.origin.truncate(), (w as f32, h as f32), tile_size);
+    (pos.x >= 0.0 && pos.y >= 0.0 && pos.x <= w as f32 && pos.y <= h as f32).then_some(pos)
+}
+
+/// Window to inject debug commands into the game: spawn and kill units, change their values, toggle cheats
+pub fn debug_panel_system(
+    mut contexts: EguiContexts,
+    queue: Res<DebugCommandQueue>,
+    mut state: ResMut<DebugPanelState>,
+    entity_system: Res<EntitySystem>,
+    selected: Res<SelectedUnit>,
+    perspective: Res<PlayerPerspective>,
+    map_res: Option<Res<MapResource>>,
+    mouse_button_input: Res<ButtonInput<MouseButton>>,
+    windows: Query<&Window>,
+    camera_query: Query<(&Camera, &GlobalTransform)>,
+) {
+    let Ok(ctx) = contexts.ctx_mut() else { return; };
+    let target = state.target_player.or(perspective.active);
+
+    // Place the picked unit on right click, unless the click is on a window
+    if state.placing && mouse_button_input.just_pressed(MouseButton::Right) && !ctx.is_pointer_over_area() {
+        let tile_size = entity_system.map_config.tile_size;
+        let position = map_res.as_ref().and_then(|m| cursor_map_position(&windows, &camera_query, m, tile_size));
+        if let (Some(unit_type), Some(pos)) = (state.unit_type, position) {
+            queue.push(target, create_unit(unit_type, state.owner, pos.x, pos.y, state.quantity));
+        }
+    }
+
+    egui::Window::new("Debug").default_open(false).default_width(320.0).show(ctx, |ui| {
+        ui.horizontal(|ui| {
+            ui.label("Send as:");
+            let mut target_player = state.target_player;
+            egui::ComboBox::from_id_salt("debug_target_player")
+                .selected_text(match target_player {
+                    Some(p) => format!("Player {}", p),
+                    None => format!("Perspective ({})", perspective.active.map(|p| p.to_string()).unwrap_or_else(|| "-".to_string())),
+                })
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut target_player, None, "Perspective");
+                    for player_id in &perspective.known_players {
+                        ui.selectable_value(&mut target_player, Some(*player_id), format!("Player {}", player_id));
+                    }
+                });
+            state.target_player = target_player;
+        });
+        ui.separator();
+
+        ui.strong("Create unit");
+        let selected_name = state.unit_type
+            .and_then(|id| entity_system.unit_name(id))
+            .unwrap_or("Pick a unit type")
+            .to_string();
+        let mut unit_type = state.unit_type;
+        egui::ComboBox::from_id_salt("debug_unit_type")
+            .selected_text(selected_name)
+            .width(240.0)
+            .show_ui(ui, |ui| {
+                ui.text_edit_singleline(&mut state.unit_filter);
+                let filter = state.unit_filter.to_lowercase();
+                let mut units: Vec<(&u32, &str)> = entity_system.unit_traits.iter()
+                    .map(|(id, data)| (id, data.name.as_str()))
+                    .filter(|(_, name)| !name.is_empty() && name.to_lowercase().contains(&filter))
+                    .collect();
+                units.sort_by(|a, b| a.1.cmp(b.1));
+                for (id, name) in units.into_iter().take(MAX_PICKER_ROWS) {
+                    ui.selectable_value(&mut unit_type, Some(*id), format!("{} ({})", name, id));
+                }
+            });
+        state.unit_type = unit_type;
+        ui.horizontal(|ui| {
+            ui.label("Owner");
+            ui.add(egui::DragValue::new(&mut state.owner).range(0..=16));
+            ui.label("Quantity");
+            ui.add(egui::DragValue::new(&mut state.quantity).range(1..=100));
+        });
+        ui.add_enabled_ui(state.unit_type.is_some(), |ui| {
+            ui.checkbox(&mut state.placing, "Right-click the map to create");
+        });
+        ui.separator();
+
+        ui.strong("Selected unit");
+        match selected.tag {
+            Some(tag) => {
+                ui.label(format!("Tag {}", tag));
+                if ui.button("Kill").clicked() {
+                    queue.push(target, kill_units(vec![tag]));
+                }
+                ui.horizontal(|ui| {
+                    ui.add(egui::DragValue::new(&mut state.value).range(0.0..=10_000.0));
+                    for (label, unit_value) in [
+                        ("Set HP", DebugSetUnitValue_UnitValue::Life),
+                        ("Set energy", DebugSetUnitValue_UnitValue::Energy),
+                        ("Set shields", DebugSetUnitValue_UnitValue::Shields),
+                    ] {
+                        if ui.button(label).clicked() {
+                            queue.push(target, set_unit_value(tag, unit_value, state.value));
+                        }
+                    }
+                });
+            }
+            None => {
+                ui.label("Click a unit to select it.");
+            }
+        }
+        ui.separator();
+
+        ui.strong("Cheats (toggle)");
+        ui.horizontal_wrapped(|ui| {
+            for (label, cheat) in [
+                ("Reveal map", DebugGameState::show_map),
+                ("God mode", DebugGameState::god),
+                ("Instant build", DebugGameState::fast_build),
+                ("Add minerals", DebugGameState::minerals),
+                ("Add gas", DebugGameState::gas),
+            ] {
+                if ui.button(label).clicked() {
+                    queue.push(target, game_state(cheat));
+                }
+            }
+        });
+
+        let pending = queue.pending();
+        if pending > 0 {
+            ui.label(format!("{} command(s) waiting for the next bot request", pending));
+        }
+    });
+}
@@ -0,0 +1,190 @@
+// src/debug_draw.rs
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use sc2_proto::common::Point;
+use sc2_proto::debug::{DebugCommand_oneof_command, DebugDraw};
+use sc2_proto::sc2api::Request_oneof_request;
+use std::collections::BTreeMap;
+use crate::app_settings::AppSettings;
+use crate::controller::{visible_for, MapResource, PlayerPerspective, ProxyGameEndedEvent, ProxyRequestEvent};
+use crate::entity_system::EntitySystem;
+use crate::units::world_position;
+
+/// SC2 draws debug text at this size when the bot leaves it unset
+const DEFAULT_TEXT_SIZE: u32 = 8;
+
+/// Latest debug draw batch of each player; a new batch replaces the previous one, as in SC2
+#[derive(Resource)]
+pub struct DebugDrawOverlay {
+    pub batches: BTreeMap<u32, DebugDraw>,
+    pub enabled: bool,
+    /// World text entities of each player's batch
+    labels: BTreeMap<u32, Vec<Entity>>,
+}
+
+impl DebugDrawOverlay {
+    pub fn new(settings: &AppSettings) -> Self {
+        Self { batches: BTreeMap::new(), enabled: settings.overlays.debug_draw, labels: BTreeMap::new() }
+    }
+}
+
+/// World-space debug text, despawned when its batch is replaced
+#[derive(Component)]
+pub struct DebugTextLabel {
+    pub player_id: u32,
+}
+
+/// Colors are 0-255 per channel; SC2 draws unset colors white
+fn debug_color(color: Option<&sc2_proto::debug::Color>) -> Color {
+    match color {
+        Some(c) => Color::srgb_u8(
+            c.r.unwrap_or(255).min(255) as u8,
+            c.g.unwrap_or(255).min(255) as u8,
+            c.b.unwrap_or(255).min(255) as u8,
+        ),
+        None => Color::WHITE,
+    }
+}
+
+fn map_point(p: &Point, map_size: (f32, f32), tile_size: f32) -> Vec2 {
+    world_position(p.get_x(), p.get_y(), map_size, tile_size)
+}
+
+/// System to collect the debug draw batches bots send, and respawn their world text
+pub fn debug_draw_capture_system(
+    mut commands: Commands,
+    mut requests: EventReader<ProxyRequestEvent>,
+    mut game_ended: EventReader<ProxyGameEndedEvent>,
+    mut overlay: ResMut<DebugDrawOverlay>,
+    mut labels: Query<(&DebugTextLabel, &mut Visibility)>,
+    map_res: Option<Res<MapResource>>,
+    entity_system: Res<EntitySystem>,
+    perspective: Res<PlayerPerspective>,
+) {
+    if game_ended.read().last().is_some() {
+        overlay.batches.clear();
+        for entity in std::mem::take(&mut overlay.labels).into_values().flatten() {
+            commands.entity(entity).despawn();
+        }
+    }
+
+    let tile_size = entity_system.map_config.tile_size;
+    for event in requests.read() {
+        let Some(Request_oneof_request::debug(debug)) = event.request.request.as_ref() else { continue; };
+        // One request may split its shapes over several draw commands
+        let mut batch: Option<DebugDraw> = None;
+        for command in debug.get_debug() {
+            if let Some(DebugCommand_oneof_command::draw(draw)) = command.command.as_ref() {
+                let batch = batch.get_or_insert_with(DebugDraw::new);
+                for text in draw.get_text() {
+                    batch.mut_text().push(text.clone());
+                }
+                for line in draw.get_lines() {
+                    batch.mut_lines().push(line.clone());
+                }
+                for debug_box in draw.get_boxes() {
+                    batch.mut_boxes().push(debug_box.clone());
+                }
+                for sphere in draw.get_spheres() {
+                    batch.mut_spheres().push(sphere.clone());
+                }
+            }
+        }
+        let Some(batch) = batch else { continue; };
+
+        for entity in overlay.labels.remove(&event.player_id).unwrap_or_default() {
+            commands.entity(entity).despawn();
+        }
+        if let Some(map_res) = map_res.as_ref() {
+            let (w, h) = map_res.static_layers.get_dimensions();
+            let map_size = (w as f32, h as f32);
+            let spawned: Vec<Entity> = batch.get_text().iter()
+                .filter(|text| text.has_world_pos())
+                .map(|text| {
+                    let pos = map_point(text.get_world_pos(), map_size, tile_size);
+                    commands.spawn((
+                        Text2d::new(text.get_text()),
+                        TextFont { font_size: text.size.unwrap_or(DEFAULT_TEXT_SIZE) as f32, ..default() },
+                        TextColor(debug_color(text.color.as_ref())),
+                        Transform::from_translation(pos.extend(6.0)),
+                        DebugTextLabel { player_id: event.player_id },
+                    )).id()
+                })
+                .collect();
+            overlay.labels.insert(event.player_id, spawned);
+        }
+        overlay.batches.insert(event.player_id, batch);
+    }
+
+    for (label, mut visibility) in labels.iter_mut() {
+        let visible = overlay.enabled && visible_for(&perspective, label.player_id);
+        *visibility = if visible { Visibility::Inherited } else { Visibility::Hidden };
+    }
+}
+
+/// System to draw the lines, boxes and spheres of the current batches
+pub fn debug_draw_gizmos_system(
+    mut gizmos: Gizmos,
+    overlay: Res<DebugDrawOverlay>,
+    map_res: Option<Res<MapResource>>,
+    entity_system: Res<EntitySystem>,
+    perspective: Res<PlayerPerspective>,
+) {
+    if !overlay.enabled {
+        return;
+    }
+    let Some(map_res) = map_res else { return; };
+    let (w, h) = map_res.static_layers.get_dimensions();
+    let map_size = (w as f32, h as f32);
+    let tile_size = entity_system.map_config.tile_size;
+
+    for (_, batch) in overlay.batches.iter().filter(|(player_id, _)| visible_for(&perspective, **player_id)) {
+        for line in batch.get_lines() {
+            let color = debug_color(line.color.as_ref());
+            let segment = line.get_line();
+            gizmos.line_2d(map_point(segment.get_p0(), map_size, tile_size), map_point(segment.get_p1(), map_size, tile_size), color);
+        }
+        // Boxes and spheres are flattened onto the map plane
+        for debug_box in batch.get_boxes() {
+            let min = map_point(debug_box.get_min(), map_size, tile_size);
+            let max = map_point(debug_box.get_max(), map_size, tile_size);
+            gizmos.rect_2d((min + max) / 2.0, (max - min).abs(), debug_color(debug_box.color.as_ref()));
+        }
+        for sphere in batch.get_spheres() {
+            let center = map_point(sphere.get_p(), map_size, tile_size);
+            gizmos.circle_2d(center, sphere.get_r() * tile_size, debug_color(sphere.color.as_ref()));
+        }
+    }
+}
+
+/// Debug text placed in screen space (`virtual_pos`, 0-1 across the window), painted over the map
+pub fn debug_draw_screen_text_system(
+    mut contexts: EguiContexts,
+    overlay: Res<DebugDrawOverlay>,
+    perspective: Res<PlayerPerspective>,
+) {
+    if !overlay.enabled || overlay.batches.is_empty() {
+        return;
+    }
+    let Ok(ctx) = contexts.ctx_mut() else { return; };
+    let screen = ctx.screen_rect();
+    let painter = ctx.layer_painter(egui::LayerId::new(egui::Order::Background, egui::Id::new("debug_draw_text")));
+
+    for (_, batch) in overlay.batches.iter().filter(|(player_id, _)| visible_for(&perspective, **player_id)) {
+        for text in batch.get_text().iter().filter(|t| !t.has_world_pos()) {
+            let pos = text.get_virtual_pos();
+            let color = debug_color(text.color.as_ref()).to_srgba();
+            painter.text(
+                screen.min + egui::vec2(pos.get_x() * screen.width(), pos.get_y() * screen.height()),
+                egui::Align2::LEFT_TOP,
+                text.get_text(),
+                egui::FontId::monospace(text.size.unwrap_or(DEFAULT_TEXT_SIZE) as f32 + 4.0),
+                egui::Color32::from_rgb(
+                    (color.red * 255.0) as u8,
+                    (color.green * 255.0) as u8,
+                    (color.blue * 255.0) as u8,
+                ),
+            );
+        }
+    }
+}
@@ -181,6 +181,18 @@ impl EntitySystem {
     }
 }
 
+/// Unit type IDs by lowercase name, from data.json alone (no assets, so it also works headless)
+pub fn load_unit_type_ids() -> Result<HashMap<String, u32>, String> {
+    let file = File::open("data/data.json")
+        .map_err(|e| format!("Failed to open data.json: {}", e))?;
+    let data: DataJson = from_reader(file)
+        .map_err(|e| format!("Failed to parse data.json: {}", e))?;
+    Ok(data.unit.into_iter()
+        .filter(|u| !u.name.is_empty())
+        .map(|u| (u.name.to_lowercase(), u.id))
+        .collect())
+}
+
 /// Startup system to initialize EntitySystem
 pub fn setup_entity_system(mut commands: Commands, asset_server: Res<AssetServer>) {
     let entity_system = EntitySystem::load(&asset_server);
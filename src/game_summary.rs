@@ -0,0 +1,116 @@
+// src/game_summary.rs
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use crate::app_settings::AppSettings;
+use crate::controller::{ProxyGameEndedEvent, ProxyResponseEvent};
+use crate::matches::{MatchRecorder, SUMMARY_FILE};
+use crate::trace::unix_millis;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlayerSummary {
+    pub player_id: u32,
+    pub player_type: String,
+    pub race: String,
+    pub name: String,
+    pub difficulty: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlayerResultSummary {
+    pub player_id: u32,
+    pub result: String,
+}
+
+/// Outcome of one game, written as JSON when the game ends
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct GameSummary {
+    pub map_name: String,
+    pub players: Vec<PlayerSummary>,
+    pub results: Vec<PlayerResultSummary>,
+    pub last_game_loop: u32,
+    pub started_at_ms: u64,
+    pub ended_at_ms: u64,
+}
+
+/// Resource accumulating the summary of the game in progress
+#[derive(Resource, Default)]
+pub struct GameSummaryTracker {
+    pub current: Option<GameSummary>,
+}
+
+/// System to build the game summary from the proxied responses
+pub fn game_summary_system(
+    mut events: EventReader<ProxyResponseEvent>,
+    mut tracker: ResMut<GameSummaryTracker>,
+) {
+    for event in events.read() {
+        let response = &event.response;
+        if response.has_game_info() {
+            let gi = response.get_game_info();
+            // Every client gets its own game_info; only the first one starts a new summary
+            if tracker.current.as_ref().is_some_and(|s| s.last_game_loop == 0 && s.map_name == gi.get_map_name()) {
+                continue;
+            }
+            tracker.current = Some(GameSummary {
+                map_name: gi.get_map_name().to_string(),
+                players: gi.get_player_info().iter().map(|p| PlayerSummary {
+                    player_id: p.get_player_id(),
+                    player_type: format!("{:?}", p.get_field_type()),
+                    race: format!("{:?}", if p.has_race_actual() { p.get_race_actual() } else { p.get_race_requested() }),
+                    name: p.get_player_name().to_string(),
+                    difficulty: p.has_difficulty().then(|| format!("{:?}", p.get_difficulty())),
+                }).collect(),
+                started_at_ms: unix_millis(),
+                ..default()
+            });
+        } else if response.has_observation() {
+            let Some(summary) = tracker.current.as_mut() else { continue; };
+            let obs = response.get_observation();
+            summary.last_game_loop = summary.last_game_loop.max(obs.get_observation().get_game_loop());
+            for result in obs.get_player_result() {
+                if summary.results.iter().any(|r| r.player_id == result.get_player_id()) {
+                    continue;
+                }
+                summary.results.push(PlayerResultSummary {
+                    player_id: result.get_player_id(),
+                    result: format!("{:?}", result.get_result()),
+                });
+            }
+        }
+    }
+}
+
+/// System to write the finished game's summary into its match directory, or next to the
+/// session traces for games that were not created by aiurgaze, and close the match
+pub fn write_game_summary_system(
+    mut events: EventReader<ProxyGameEndedEvent>,
+    mut tracker: ResMut<GameSummaryTracker>,
+    mut matches: ResMut<MatchRecorder>,
+    settings: Res<AppSettings>,
+) {
+    if events.read().last().is_none() {
+        return;
+    }
+    let match_dir = matches.finish();
+    let Some(mut summary) = tracker.current.take() else { return; };
+    summary.ended_at_ms = unix_millis();
+
+    let (dir, path) = match match_dir {
+        Some(dir) => (dir.clone(), dir.join(SUMMARY_FILE)),
+        None => {
+            let dir = PathBuf::from(&settings.trace.dir);
+            let path = dir.join(format!("summary-{}.json", summary.ended_at_ms));
+            (dir, path)
+        }
+    };
+    let result = fs::create_dir_all(&dir)
+        .map_err(|e| e.to_string())
+        .and_then(|_| serde_json::to_string_pretty(&summary).map_err(|e| e.to_string()))
+        .and_then(|json| fs::write(&path, json).map_err(|e| e.to_string()));
+    match result {
+        Ok(_) => println!("[game_summary] Wrote game summary to {}", path.display()),
+        Err(e) => eprintln!("[game_summary] Failed to write {}: {}", path.display(), e),
+    }
+}
@@ -0,0 +1,290 @@
+// src/inspector.rs
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use protobuf::reflect::{ReflectFieldRef, ReflectValueRef};
+use protobuf::Message;
+use sc2_proto::sc2api::{Request, Response};
+use std::collections::{BTreeSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use crate::app_settings::AppSettings;
+use crate::proxy_ws::ProxyMessage;
+use crate::trace::TraceDirection;
+
+/// Messages copied by the proxy sessions, waiting to be moved into `ProtocolLog` on the main thread
+#[derive(Resource, Clone, Default)]
+pub struct ProtocolMessageBuffer(pub Arc<Mutex<Vec<ProxyMessage>>>);
+
+/// A node of a decoded message: a scalar field, or a message with its set fields as children
+#[derive(Debug, Clone)]
+pub struct FieldNode {
+    pub name: String,
+    pub value: Option<String>,
+    pub children: Vec<FieldNode>,
+}
+
+fn value_node(name: String, value: ReflectValueRef) -> FieldNode {
+    match value {
+        ReflectValueRef::Message(m) => FieldNode { name, value: None, children: message_field_tree(m) },
+        ReflectValueRef::Enum(e) => FieldNode { name, value: Some(e.name().to_string()), children: Vec::new() },
+        ReflectValueRef::String(s) => FieldNode { name, value: Some(format!("{:?}", s)), children: Vec::new() },
+        ReflectValueRef::Bytes(b) => FieldNode { name, value: Some(format!("<{} bytes>", b.len())), children: Vec::new() },
+        other => FieldNode { name, value: Some(format!("{:?}", other)), children: Vec::new() },
+    }
+}
+
+/// Set fields of a message as a tree, using the same reflection as `units::get_set_fields`
+pub fn message_field_tree(message: &dyn Message) -> Vec<FieldNode> {
+    let descriptor = message.descriptor();
+    let mut result = Vec::new();
+    for field in descriptor.fields() {
+        match field.get_reflect(message) {
+            ReflectFieldRef::Optional(Some(value)) if field.has_field(message) => {
+                result.push(value_node(field.name().to_string(), value));
+            }
+            ReflectFieldRef::Repeated(r) if r.len() > 0 => {
+                let children = (0..r.len())
+                    .map(|i| value_node(format!("[{}]", i), r.get(i).as_ref()))
+                    .collect();
+                result.push(FieldNode {
+                    name: format!("{} ({})", field.name(), r.len()),
+                    value: None,
+                    children,
+                });
+            }
+            _ => continue,
+        }
+    }
+    result
+}
+
+fn decode_tree(message: &LoggedMessage) -> Vec<FieldNode> {
+    let decoded: Result<Box<dyn Message>, _> = match message.direction {
+        TraceDirection::Request => Request::parse_from_bytes(&message.payload).map(|m| Box::new(m) as Box<dyn Message>),
+        TraceDirection::Response => Response::parse_from_bytes(&message.payload).map(|m| Box::new(m) as Box<dyn Message>),
+    };
+    match decoded {
+        Ok(m) => message_field_tree(m.as_ref()),
+        Err(e) => vec![FieldNode { name: "decode error".to_string(), value: Some(e.to_string()), children: Vec::new() }],
+    }
+}
+
+/// Flatten a tree into `name: value` lines, for full-text search
+fn tree_text(nodes: &[FieldNode], out: &mut String) {
+    for node in nodes {
+        out.push_str(&node.name);
+        if let Some(value) = &node.value {
+            out.push_str(": ");
+            out.push_str(value);
+        }
+        out.push('\n');
+        tree_text(&node.children, out);
+    }
+}
+
+#[derive(Debug)]
+pub struct LoggedMessage {
+    pub id: u64,
+    pub player_id: u32,
+    pub direction: TraceDirection,
+    pub kind: &'static str,
+    pub game_loop: u32,
+    pub latency_ms: Option<f64>,
+    pub payload: Vec<u8>,
+    /// Lower-cased decoded text, built the first time a search needs it
+    search_text: Option<String>,
+}
+
+impl LoggedMessage {
+    fn matches(&mut self, hidden_kinds: &BTreeSet<&'static str>, search: &str) -> bool {
+        if hidden_kinds.contains(self.kind) {
+            return false;
+        }
+        if search.is_empty() {
+            return true;
+        }
+        if self.search_text.is_none() {
+            let mut text = String::new();
+            tree_text(&decode_tree(self), &mut text);
+            self.search_text = Some(text.to_lowercase());
+        }
+        self.kind.contains(search) || self.search_text.as_deref().is_some_and(|text| text.contains(search))
+    }
+}
+
+/// Resource holding the recent protocol messages and the inspector's filters
+#[derive(Resource, Default)]
+pub struct ProtocolLog {
+    pub messages: VecDeque<LoggedMessage>,
+    pub kinds: BTreeSet<&'static str>,
+    hidden_kinds: BTreeSet<&'static str>,
+    search: String,
+    paused: bool,
+    next_id: u64,
+    /// IDs of the messages passing the filters, oldest first
+    filtered: Vec<u64>,
+    filters_changed: bool,
+    selected: Option<(u64, Vec<FieldNode>)>,
+}
+
+impl ProtocolLog {
+    fn refilter(&mut self) {
+        let search = self.search.to_lowercase();
+        let hidden = &self.hidden_kinds;
+        self.filtered = self.messages.iter_mut()
+            .filter_map(|m| m.matches(hidden, &search).then_some(m.id))
+            .collect();
+        self.filters_changed = false;
+    }
+
+    fn get(&self, id: u64) -> Option<&LoggedMessage> {
+        let front = self.messages.front()?.id;
+        self.messages.get(id.checked_sub(front)? as usize)
+    }
+}
+
+/// System to move messages from the proxy into the log, dropping the oldest beyond the limit
+pub fn protocol_log_system(
+    buffer: Res<ProtocolMessageBuffer>,
+    mut log: ResMut<ProtocolLog>,
+    settings: Res<AppSettings>,
+) {
+    let incoming = std::mem::take(&mut *buffer.0.lock().unwrap());
+    if log.paused {
+        return;
+    }
+    let search = log.search.to_lowercase();
+    for message in incoming {
+        let id = log.next_id;
+        log.next_id += 1;
+        log.kinds.insert(message.kind);
+        let mut logged = LoggedMessage {
+            id,
+            player_id: message.player_id,
+            direction: message.direction,
+            kind: message.kind,
+            game_loop: message.game_loop,
+            latency_ms: message.latency_ms,
+            payload: message.payload,
+            search_text: None,
+        };
+        if logged.matches(&log.hidden_kinds, &search) {
+            log.filtered.push(id);
+        }
+        log.messages.push_back(logged);
+    }
+
+    let max = settings.inspector.max_messages.max(1);
+    while log.messages.len() > max {
+        log.messages.pop_front();
+    }
+    if let Some(front) = log.messages.front().map(|m| m.id) {
+        let stale = log.filtered.partition_point(|&id| id < front);
+        log.filtered.drain(..stale);
+    }
+}
+
+fn show_tree(ui: &mut egui::Ui, nodes: &[FieldNode], path: &str) {
+    for (i, node) in nodes.iter().enumerate() {
+        if node.children.is_empty() {
+            ui.label(format!("{}: {}", node.name, node.value.as_deref().unwrap_or("")));
+        } else {
+            let id = format!("{}/{}", path, i);
+            egui::CollapsingHeader::new(&node.name)
+                .id_salt(&id)
+                .show(ui, |ui| show_tree(ui, &node.children, &id));
+        }
+    }
+}
+
+/// Window listing the proxied messages, with filters and a field tree of the selected one.
+/// Only shown when `[inspector] enabled` is set, since nothing is recorded otherwise.
+pub fn protocol_inspector_system(mut contexts: EguiContexts, mut log: ResMut<ProtocolLog>, settings: Res<AppSettings>) {
+    if !settings.inspector.enabled {
+        return;
+    }
+    let Ok(ctx) = contexts.ctx_mut() else { return; };
+
+    egui::Window::new("Protocol inspector")
+        .default_open(false)
+        .default_size([520.0, 600.0])
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut log.paused, "Pause");
+                if ui.button("Clear").clicked() {
+                    log.messages.clear();
+                    log.filtered.clear();
+                    log.selected = None;
+                }
+                ui.menu_button("Types", |ui| {
+                    let kinds: Vec<&'static str> = log.kinds.iter().copied().collect();
+                    for kind in kinds {
+                        let mut shown = !log.hidden_kinds.contains(kind);
+                        if ui.checkbox(&mut shown, kind).changed() {
+                            if shown {
+                                log.hidden_kinds.remove(kind);
+                            } else {
+                                log.hidden_kinds.insert(kind);
+                            }
+                            log.filters_changed = true;
+                        }
+                    }
+                });
+                ui.label("Search:");
+                if ui.text_edit_singleline(&mut log.search).changed() {
+                    log.filters_changed = true;
+                }
+            });
+            if log.filters_changed {
+                log.refilter();
+            }
+            ui.label(format!("{} of {} messages", log.filtered.len(), log.messages.len()));
+            ui.separator();
+
+            let row_height = ui.text_style_height(&egui::TextStyle::Monospace) + 2.0;
+            let mut clicked = None;
+            egui::ScrollArea::vertical()
+                .id_salt("protocol_messages")
+                .max_height(280.0)
+                .auto_shrink([false, false])
+                .stick_to_bottom(true)
+                .show_rows(ui, row_height, log.filtered.len(), |ui, rows| {
+                    for &id in &log.filtered[rows] {
+                        let Some(message) = log.get(id) else { continue; };
+                        let arrow = match message.direction {
+                            TraceDirection::Request => "→",
+                            TraceDirection::Response => "←",
+                        };
+                        let latency = message.latency_ms.map(|ms| format!("{:.1} ms", ms)).unwrap_or_default();
+                        let text = format!(
+                            "{:>6} P{} {} {:<14} {:>8} B  loop {:<6} {}",
+                            id, message.player_id, arrow, message.kind, message.payload.len(), message.game_loop, latency
+                        );
+                        let selected = log.selected.as_ref().is_some_and(|(s, _)| *s == id);
+                        if ui.selectable_label(selected, egui::RichText::new(text).monospace()).clicked() {
+                            clicked = Some(id);
+                        }
+                    }
+                });
+
+            if let Some(id) = clicked {
+                if let Some(message) = log.get(id) {
+                    let tree = decode_tree(message);
+                    log.selected = Some((id, tree));
+                }
+            }
+
+            ui.separator();
+            match &log.selected {
+                Some((id, tree)) => {
+                    ui.strong(format!("Message {}", id));
+                    egui::ScrollArea::vertical()
+                        .id_salt("protocol_message_tree")
+                        .auto_shrink([false, false])
+                        .show(ui, |ui| show_tree(ui, tree, &id.to_string()));
+                }
+                None => {
+                    ui.label("Click a message to decode it.");
+                }
+            }
+        });
+}
@@ -0,0 +1,379 @@
+// src/ladder.rs
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet, VecDeque};
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+use crate::app_settings::{LadderBot, LadderConfig, PairingMode};
+use crate::controller::{ProxyGameEndedEvent, ProxyResponseEvent};
+use sc2_proto::common::Race;
+use sc2_proto::sc2api::Result as GameResult;
+use crate::ui::{build_create_game_request, CreateGameFailedEvent, GameConfigPanel, GameType, PendingCreateGameRequest};
+use crate::ui::game_config_panel::parse_race;
+
+/// The home bot is the first entry of `player_setup`, so the server gives it player ID 1
+const HOME_PLAYER_ID: u32 = 1;
+
+const INITIAL_RATING: f64 = 1500.0;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BotRating {
+    pub rating: f64,
+    pub games: u32,
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+}
+
+impl Default for BotRating {
+    fn default() -> Self {
+        Self { rating: INITIAL_RATING, games: 0, wins: 0, losses: 0, draws: 0 }
+    }
+}
+
+/// Elo ratings of every bot that played, persisted between sessions
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LadderRatings {
+    pub bots: BTreeMap<String, BotRating>,
+}
+
+impl LadderRatings {
+    /// Ratings saved at `path`, or fresh ones if there is no file yet. A file that can't be read
+    /// or parsed is an error: starting fresh would overwrite it with the next save.
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Self::default()),
+            Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+        };
+        serde_json::from_str(&content)
+            .map_err(|e| format!("Failed to parse {}: {}; fix or move it to start with fresh ratings", path.display(), e))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(path, json).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    pub fn rating(&self, name: &str) -> f64 {
+        self.bots.get(name).map_or(INITIAL_RATING, |r| r.rating)
+    }
+
+    /// Apply one game's outcome; `score` is 1.0 for a home win, 0.0 for a loss and 0.5 for a draw
+    pub fn record(&mut self, home: &str, away: &str, score: f64, k_factor: f64) {
+        let (home_rating, away_rating) = (self.rating(home), self.rating(away));
+        let expected = 1.0 / (1.0 + 10f64.powf((away_rating - home_rating) / 400.0));
+        let delta = k_factor * (score - expected);
+
+        for (name, change, score) in [(home, delta, score), (away, -delta, 1.0 - score)] {
+            let entry = self.bots.entry(name.to_string()).or_default();
+            entry.rating += change;
+            entry.games += 1;
+            match score {
+                s if s > 0.5 => entry.wins += 1,
+                s if s < 0.5 => entry.losses += 1,
+                _ => entry.draws += 1,
+            }
+        }
+    }
+}
+
+/// One scheduled game: indexes into the roster
+#[derive(Debug, Clone, Copy)]
+pub struct Pairing {
+    pub home: usize,
+    pub away: usize,
+}
+
+/// Resource scheduling ladder games and tracking ratings
+#[derive(Resource)]
+pub struct LadderRunner {
+    pub config: LadderConfig,
+    pub ratings: LadderRatings,
+    pub queue: VecDeque<Pairing>,
+    pub current: Option<Pairing>,
+    pub round: u32,
+    pub games_played: usize,
+    /// Pairs that already met, used to avoid rematches in Swiss rounds
+    played_pairs: HashSet<(usize, usize)>,
+    current_score: Option<f64>,
+}
+
+impl LadderRunner {
+    pub fn new(config: LadderConfig) -> Result<Self, String> {
+        if config.bots.len() < 2 {
+            return Err("The ladder needs at least two bots in [[ladder.bots]]".to_string());
+        }
+        if config.maps.is_empty() {
+            return Err("The ladder needs at least one map in [ladder] maps".to_string());
+        }
+        let ratings = LadderRatings::load(Path::new(&config.ratings_file))?;
+        let mut runner = Self {
+            config,
+            ratings,
+            queue: VecDeque::new(),
+            current: None,
+            round: 0,
+            games_played: 0,
+            played_pairs: HashSet::new(),
+            current_score: None,
+        };
+        runner.schedule_next_round();
+        Ok(runner)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.current.is_none() && self.queue.is_empty() && self.round >= self.config.rounds
+    }
+
+    /// Fill the queue with the pairings of the next round, if rounds are left
+    fn schedule_next_round(&mut self) {
+        if self.round >= self.config.rounds {
+            return;
+        }
+        self.round += 1;
+        let count = self.config.bots.len();
+        match self.config.pairing {
+            PairingMode::RoundRobin => {
+                for home in 0..count {
+                    for away in (home + 1)..count {
+                        // Swap sides every round so both bots play as player 1
+                        let pairing = if self.round % 2 == 1 { Pairing { home, away } } else { Pairing { home: away, away: home } };
+                        self.queue.push_back(pairing);
+                    }
+                }
+            }
+            PairingMode::Swiss => {
+                // Pair neighbours in the current standings, skipping rematches when possible
+                let mut order: Vec<usize> = (0..count).collect();
+                order.sort_by(|a, b| {
+                    let (ra, rb) = (self.ratings.rating(&self.config.bots[*a].name), self.ratings.rating(&self.config.bots[*b].name));
+                    rb.partial_cmp(&ra).unwrap_or(std::cmp::Ordering::Equal)
+                });
+                while order.len() >= 2 {
+                    let home = order.remove(0);
+                    let pos = order.iter()
+                        .position(|&away| !self.played_pairs.contains(&(home.min(away), home.max(away))))
+                        .unwrap_or(0);
+                    let away = order.remove(pos);
+                    self.queue.push_back(Pairing { home, away });
+                }
+                if let Some(bye) = order.first() {
+                    println!("[ladder] {} has a bye in round {}", self.config.bots[*bye].name, self.round);
+                }
+            }
+        }
+        println!("[ladder] Round {} scheduled with {} games", self.round, self.queue.len());
+    }
+
+    /// Roster entries sorted by rating, best first
+    pub fn standings(&self) -> Vec<(String, BotRating)> {
+        let mut standings: Vec<(String, BotRating)> = self.config.bots.iter()
+            .map(|bot| (bot.name.clone(), self.ratings.bots.get(&bot.name).cloned().unwrap_or_default()))
+            .collect();
+        standings.sort_by(|a, b| b.1.rating.partial_cmp(&a.1.rating).unwrap_or(std::cmp::Ordering::Equal));
+        standings
+    }
+}
+
+/// Point the game config panel at a bot-vs-bot game between two roster entries.
+/// Every field is set, so nothing of the previous pairing (e.g. a race) carries over;
+/// only the lists and the game options from the config are kept.
+fn configure_panel(panel: &mut GameConfigPanel, home: &LadderBot, away: &LadderBot, map: &str) {
+    *panel = GameConfigPanel {
+        game_type: GameType::VsBot,
+        map_name: Some(map.to_string()),
+        available_maps: std::mem::take(&mut panel.available_maps),
+        player_name: home.name.clone(),
+        player_race: home.race.as_deref().and_then(parse_race).unwrap_or(Race::Random),
+        ai_difficulty: None,
+        ai_race: Some(away.race.as_deref().and_then(parse_race).unwrap_or(Race::Random)),
+        ai_build: None,
+        bot_name: Some(away.name.clone()),
+        disable_fog: panel.disable_fog,
+        random_seed: panel.random_seed,
+        realtime: panel.realtime,
+        bot_command: home.command.clone(),
+        bot_opponent_command: away.command.clone(),
+        player_bot: None,
+        opponent_bot: None,
+        available_bots: std::mem::take(&mut panel.available_bots),
+        auto_restart: false,
+        scenario: None,
+    };
+}
+
+/// System to run ladder pairings one after the other and update ratings from their results
+pub fn ladder_system(
+    mut runner: ResMut<LadderRunner>,
+    mut responses: EventReader<ProxyResponseEvent>,
+    mut game_ended: EventReader<ProxyGameEndedEvent>,
+    mut create_failed: EventReader<CreateGameFailedEvent>,
+    mut panel: ResMut<GameConfigPanel>,
+    mut pending_request: ResMut<PendingCreateGameRequest>,
+) {
+    for event in responses.read() {
+        if event.player_id != HOME_PLAYER_ID || !event.response.has_observation() {
+            continue;
+        }
+        let obs = event.response.get_observation();
+        if let Some(result) = obs.get_player_result().iter().find(|r| r.get_player_id() == HOME_PLAYER_ID) {
+            runner.current_score = match result.get_result() {
+                GameResult::Victory => Some(1.0),
+                GameResult::Defeat => Some(0.0),
+                GameResult::Tie => Some(0.5),
+                GameResult::Undecided => None,
+            };
+        }
+    }
+
+    // A game that never started has no game end to wait for; it counts as played but isn't rated
+    if let Some(CreateGameFailedEvent(e)) = create_failed.read().last() {
+        if let Some(pairing) = runner.current.take() {
+            eprintln!("[ladder] {} vs {} could not be created: {}",
+                runner.config.bots[pairing.home].name, runner.config.bots[pairing.away].name, e);
+            runner.games_played += 1;
+            runner.current_score = None;
+        }
+    }
+
+    if game_ended.read().last().is_some() {
+        if let Some(pairing) = runner.current.take() {
+            let home = runner.config.bots[pairing.home].name.clone();
+            let away = runner.config.bots[pairing.away].name.clone();
+            runner.played_pairs.insert((pairing.home.min(pairing.away), pairing.home.max(pairing.away)));
+            runner.games_played += 1;
+
+            // A game without a result (crash, disconnect) doesn't move the ratings
+            if let Some(score) = runner.current_score.take() {
+                let k_factor = runner.config.k_factor;
+                runner.ratings.record(&home, &away, score, k_factor);
+                println!("[ladder] {} vs {}: {}", home, away, score);
+                if let Err(e) = runner.ratings.save(Path::new(&runner.config.ratings_file)) {
+                    eprintln!("[ladder] {}", e);
+                }
+            } else {
+                eprintln!("[ladder] {} vs {} ended without a result, not rated", home, away);
+            }
+        }
+    }
+
+    if runner.current.is_some() || pending_request.0.is_some() {
+        return;
+    }
+    if runner.queue.is_empty() {
+        runner.schedule_next_round();
+    }
+    let Some(pairing) = runner.queue.pop_front() else { return; };
+
+    let map = runner.config.maps[runner.games_played % runner.config.maps.len()].clone();
+    let (home, away) = (&runner.config.bots[pairing.home], &runner.config.bots[pairing.away]);
+    configure_panel(&mut panel, home, away, &map);
+    match build_create_game_request(&panel) {
+        Ok(req) => {
+            println!("[ladder] Round {}: {} vs {} on {}", runner.round, home.name, away.name, map);
+            pending_request.0 = Some(req);
+            runner.current = Some(pairing);
+            runner.current_score = None;
+        }
+        Err(e) => eprintln!("[ladder] Skipping {} vs {}: {}", home.name, away.name, e),
+    }
+}
+
+/// Window with the ladder standings
+pub fn ladder_panel_system(mut contexts: EguiContexts, runner: Res<LadderRunner>) {
+    let Ok(ctx) = contexts.ctx_mut() else { return; };
+
+    egui::Window::new("Ladder").default_open(true).show(ctx, |ui| {
+        ui.label(format!("Round {} / {}, {} games played", runner.round, runner.config.rounds, runner.games_played));
+        if let Some(pairing) = runner.current {
+            ui.label(format!(
+                "Playing: {} vs {}",
+                runner.config.bots[pairing.home].name, runner.config.bots[pairing.away].name
+            ));
+        }
+        ui.separator();
+        egui::Grid::new("ladder_standings").striped(true).show(ui, |ui| {
+            ui.strong("#");
+            ui.strong("Bot");
+            ui.strong("Rating");
+            ui.strong("W");
+            ui.strong("L");
+            ui.strong("D");
+            ui.end_row();
+            for (rank, (name, rating)) in runner.standings().into_iter().enumerate() {
+                ui.label((rank + 1).to_string());
+                ui.label(name);
+                ui.label(format!("{:.0}", rating.rating));
+                ui.label(rating.wins.to_string());
+                ui.label(rating.losses.to_string());
+                ui.label(rating.draws.to_string());
+                ui.end_row();
+            }
+        });
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn equal_ratings_move_by_half_the_k_factor() {
+        let mut ratings = LadderRatings::default();
+        ratings.record("home", "away", 1.0, 32.0);
+
+        assert_eq!(ratings.rating("home"), INITIAL_RATING + 16.0);
+        assert_eq!(ratings.rating("away"), INITIAL_RATING - 16.0);
+        let (home, away) = (&ratings.bots["home"], &ratings.bots["away"]);
+        assert_eq!((home.games, home.wins, home.losses, home.draws), (1, 1, 0, 0));
+        assert_eq!((away.games, away.wins, away.losses, away.draws), (1, 0, 1, 0));
+    }
+
+    #[test]
+    fn draw_between_equal_ratings_changes_nothing() {
+        let mut ratings = LadderRatings::default();
+        ratings.record("home", "away", 0.5, 32.0);
+
+        assert_eq!(ratings.rating("home"), INITIAL_RATING);
+        assert_eq!(ratings.rating("away"), INITIAL_RATING);
+        assert_eq!(ratings.bots["home"].draws, 1);
+        assert_eq!(ratings.bots["away"].draws, 1);
+    }
+
+    #[test]
+    fn home_loss_counts_for_the_away_bot() {
+        let mut ratings = LadderRatings::default();
+        ratings.record("home", "away", 0.0, 32.0);
+
+        assert_eq!(ratings.rating("home"), INITIAL_RATING - 16.0);
+        assert_eq!(ratings.bots["home"].losses, 1);
+        assert_eq!(ratings.bots["away"].wins, 1);
+    }
+
+    #[test]
+    fn upset_moves_ratings_more_than_an_expected_win() {
+        let mut ratings = LadderRatings::default();
+        ratings.bots.insert("strong".to_string(), BotRating { rating: 1700.0, ..BotRating::default() });
+        ratings.record("weak", "strong", 1.0, 32.0);
+
+        let gain = ratings.rating("weak") - INITIAL_RATING;
+        assert!(gain > 16.0 && gain < 32.0, "gain {}", gain);
+        assert!((ratings.rating("strong") - (1700.0 - gain)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn missing_ratings_file_starts_fresh_but_a_broken_one_is_an_error() {
+        let dir = std::env::temp_dir().join(format!("aiurgaze-ladder-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("ratings.json");
+        fs::remove_file(&path).ok();
+
+        assert!(LadderRatings::load(&path).unwrap().bots.is_empty());
+        fs::write(&path, "{ not json").unwrap();
+        assert!(LadderRatings::load(&path).is_err());
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,237 @@
+// src/latency.rs
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use crate::app_settings::AppSettings;
+use crate::controller::ProxyGameEndedEvent;
+use crate::matches::MatchRecorder;
+use crate::proxy_ws::{LatencySample, LatencySource};
+use crate::trace::unix_millis;
+
+pub const LATENCY_FILE: &str = "latency.csv";
+
+/// Samples kept for the current game; a long game at one sample per step stays well below this
+const MAX_SAMPLES: usize = 500_000;
+/// Samples shown in the rolling graph
+const ROLLING_WINDOW: usize = 500;
+const HISTOGRAM_BUCKETS: usize = 30;
+
+/// Samples measured by the proxy sessions, waiting to be moved into `LatencyStats` on the main thread
+#[derive(Resource, Clone, Default)]
+pub struct LatencyBuffer(pub Arc<Mutex<Vec<LatencySample>>>);
+
+/// One line of the latency panel: bot think time of a player, or server time of a request type
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LatencySeries {
+    Bot { player_id: u32 },
+    Server { request: &'static str },
+}
+
+impl LatencySeries {
+    fn of(sample: &LatencySample) -> Self {
+        match sample.source {
+            LatencySource::Bot => LatencySeries::Bot { player_id: sample.player_id },
+            LatencySource::Server => LatencySeries::Server { request: sample.request },
+        }
+    }
+
+    fn label(&self) -> String {
+        match self {
+            LatencySeries::Bot { player_id } => format!("Bot step time (player {})", player_id),
+            LatencySeries::Server { request } => format!("Server: {}", request),
+        }
+    }
+}
+
+/// Latency samples of the game in progress
+#[derive(Resource, Default)]
+pub struct LatencyStats {
+    pub samples: Vec<LatencySample>,
+    pub series: BTreeSet<LatencySeries>,
+    selected: Option<LatencySeries>,
+}
+
+impl LatencyStats {
+    fn values(&self, series: &LatencySeries) -> Vec<&LatencySample> {
+        self.samples.iter().filter(|s| &LatencySeries::of(s) == series).collect()
+    }
+
+    /// Write every sample as CSV
+    pub fn export(&self, path: &Path) -> Result<(), String> {
+        let mut csv = String::from("player_id,game_loop,source,request,millis\n");
+        for s in &self.samples {
+            let source = match s.source {
+                LatencySource::Bot => "bot",
+                LatencySource::Server => "server",
+            };
+            csv.push_str(&format!("{},{},{},{},{:.3}\n", s.player_id, s.game_loop, source, s.request, s.millis));
+        }
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        }
+        fs::write(path, csv).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+}
+
+/// Value at fraction `p` of an ascending slice
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let index = ((sorted.len() - 1) as f64 * p).round() as usize;
+    sorted[index]
+}
+
+/// System to collect latency samples and export them into the match directory when the game ends
+pub fn latency_system(
+    buffer: Res<LatencyBuffer>,
+    mut stats: ResMut<LatencyStats>,
+    mut game_ended: EventReader<ProxyGameEndedEvent>,
+    matches: Res<MatchRecorder>,
+    settings: Res<AppSettings>,
+) {
+    let samples = std::mem::take(&mut *buffer.0.lock().unwrap());
+    for sample in samples {
+        stats.series.insert(LatencySeries::of(&sample));
+        stats.samples.push(sample);
+    }
+    if stats.samples.len() > MAX_SAMPLES {
+        let excess = stats.samples.len() - MAX_SAMPLES;
+        stats.samples.drain(..excess);
+    }
+
+    if game_ended.read().last().is_none() || stats.samples.is_empty() {
+        return;
+    }
+    let path = match &matches.current {
+        Some(dir) => dir.join(LATENCY_FILE),
+        None => PathBuf::from(&settings.trace.dir).join(format!("latency-{}.csv", unix_millis())),
+    };
+    match stats.export(&path) {
+        Ok(()) => println!("[latency] Wrote latency samples to {}", path.display()),
+        Err(e) => eprintln!("[latency] {}", e),
+    }
+    *stats = LatencyStats::default();
+}
+
+/// Window with a histogram and rolling graph of the selected latency series
+pub fn latency_panel_system(
+    mut contexts: EguiContexts,
+    mut stats: ResMut<LatencyStats>,
+    settings: Res<AppSettings>,
+) {
+    if stats.samples.is_empty() {
+        return;
+    }
+    let Ok(ctx) = contexts.ctx_mut() else { return; };
+    let step_limit = (settings.latency.step_limit_ms > 0.0).then_some(settings.latency.step_limit_ms);
+
+    egui::Window::new("Latency").default_open(false).default_width(380.0).show(ctx, |ui| {
+        let mut selected = stats.selected.clone()
+            .or_else(|| stats.series.iter().next().cloned());
+        egui::ComboBox::from_id_salt("latency_series_combo")
+            .selected_text(selected.as_ref().map(|s| s.label()).unwrap_or_default())
+            .show_ui(ui, |ui| {
+                for series in &stats.series {
+                    ui.selectable_value(&mut selected, Some(series.clone()), series.label());
+                }
+            });
+        let Some(series) = selected else { return; };
+
+        let samples = stats.values(&series);
+        let mut sorted: Vec<f64> = samples.iter().map(|s| s.millis).collect();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+        let mean = sorted.iter().sum::<f64>() / sorted.len().max(1) as f64;
+        ui.label(format!(
+            "n={}  mean {:.1} ms  p50 {:.1}  p95 {:.1}  p99 {:.1}  max {:.1}",
+            sorted.len(), mean, percentile(&sorted, 0.5), percentile(&sorted, 0.95), percentile(&sorted, 0.99),
+            sorted.last().copied().unwrap_or(0.0),
+        ));
+        // The ladder limit applies to the bot's step time only
+        let limit = match series {
+            LatencySeries::Bot { .. } => step_limit,
+            LatencySeries::Server { .. } => None,
+        };
+        if let Some(limit) = limit {
+            let over = sorted.iter().filter(|&&v| v > limit).count();
+            let color = if over > 0 { egui::Color32::from_rgb(255, 120, 80) } else { egui::Color32::GRAY };
+            ui.colored_label(color, format!("{} steps over the {:.0} ms limit", over, limit));
+        }
+
+        ui.label("Histogram (ms)");
+        draw_histogram(ui, &sorted, limit);
+        ui.label("Rolling (ms by game loop)");
+        let recent = &samples[samples.len().saturating_sub(ROLLING_WINDOW)..];
+        draw_rolling(ui, recent, limit);
+
+        if ui.button("Export CSV").clicked() {
+            let path = PathBuf::from(&settings.trace.dir).join(format!("latency-{}.csv", unix_millis()));
+            match stats.export(&path) {
+                Ok(()) => println!("[latency] Exported latency samples to {}", path.display()),
+                Err(e) => eprintln!("[latency] {}", e),
+            }
+        }
+        stats.selected = Some(series);
+    });
+}
+
+fn draw_histogram(ui: &mut egui::Ui, sorted: &[f64], limit: Option<f64>) {
+    let (rect, _) = ui.allocate_exact_size(egui::vec2(ui.available_width(), 100.0), egui::Sense::hover());
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 2.0, egui::Color32::from_gray(20));
+    if sorted.is_empty() {
+        return;
+    }
+    // Cap the range at p99 so a few outliers don't squash the rest into one bucket
+    let range = percentile(sorted, 0.99).max(limit.unwrap_or(0.0)).max(1e-3);
+    let mut buckets = [0usize; HISTOGRAM_BUCKETS];
+    for &v in sorted {
+        let index = ((v / range) * HISTOGRAM_BUCKETS as f64) as usize;
+        buckets[index.min(HISTOGRAM_BUCKETS - 1)] += 1;
+    }
+    let tallest = *buckets.iter().max().unwrap_or(&1) as f32;
+    let width = rect.width() / HISTOGRAM_BUCKETS as f32;
+    for (i, &count) in buckets.iter().enumerate() {
+        let height = count as f32 / tallest * rect.height();
+        let bar = egui::Rect::from_min_max(
+            egui::pos2(rect.left() + i as f32 * width + 1.0, rect.bottom() - height),
+            egui::pos2(rect.left() + (i + 1) as f32 * width - 1.0, rect.bottom()),
+        );
+        painter.rect_filled(bar, 0.0, egui::Color32::from_rgb(100, 180, 255));
+    }
+    if let Some(limit) = limit {
+        let x = rect.left() + (limit / range) as f32 * rect.width();
+        painter.vline(x, rect.y_range(), egui::Stroke::new(1.0, egui::Color32::from_rgb(220, 80, 80)));
+    }
+    painter.text(rect.right_top(), egui::Align2::RIGHT_TOP, format!("{:.1} ms", range), egui::FontId::monospace(10.0), egui::Color32::GRAY);
+}
+
+fn draw_rolling(ui: &mut egui::Ui, samples: &[&LatencySample], limit: Option<f64>) {
+    let (rect, _) = ui.allocate_exact_size(egui::vec2(ui.available_width(), 80.0), egui::Sense::hover());
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 2.0, egui::Color32::from_gray(20));
+    if samples.len() < 2 {
+        return;
+    }
+    let max = samples.iter().map(|s| s.millis).fold(limit.unwrap_or(0.0), f64::max).max(1e-3) * 1.1;
+    let step = rect.width() / (samples.len() - 1) as f32;
+    let to_y = |v: f64| rect.bottom() - (v / max) as f32 * rect.height();
+    if let Some(limit) = limit {
+        painter.hline(rect.x_range(), to_y(limit), egui::Stroke::new(1.0, egui::Color32::from_rgb(220, 80, 80)));
+    }
+    let points: Vec<egui::Pos2> = samples.iter().enumerate()
+        .map(|(i, s)| egui::pos2(rect.left() + i as f32 * step, to_y(s.millis)))
+        .collect();
+    painter.add(egui::Shape::line(points, egui::Stroke::new(1.5, egui::Color32::from_rgb(100, 180, 255))));
+    painter.text(rect.left_top(), egui::Align2::LEFT_TOP, format!("{:.1} ms", max), egui::FontId::monospace(10.0), egui::Color32::GRAY);
+    painter.text(
+        rect.right_bottom(),
+        egui::Align2::RIGHT_BOTTOM,
+        format!("loops {}-{}", samples[0].game_loop, samples[samples.len() - 1].game_loop),
+        egui::FontId::monospace(10.0),
+        egui::Color32::GRAY,
+    );
+}
@@ -10,6 +10,23 @@ mod net_helpers;
 mod app_settings;
 mod entity_system;
 mod bot_runner;
+mod trace;
+mod replay;
+mod game_summary;
+mod series;
+mod ladder;
+mod matches;
+mod bot_resources;
+mod inspector;
+mod latency;
+mod action_errors;
+mod bot_actions;
+mod debug_draw;
+mod step_control;
+mod debug_commands;
+mod scenario;
+mod assertions;
+mod mock_sc2;
 
 use bevy::prelude::*;
 use bevy_health_bar3d::prelude::*;
@@ -19,20 +36,42 @@ use bevy_ecs_tilemap::{ TilemapPlugin};
 use bevy_egui::{EguiPlugin, EguiPrimaryContextPass};
 use bevy_tokio_tasks::{TokioTasksPlugin, TokioTasksRuntime};
 use tap::prelude::*;
-use crate::controller::{response_controller_system, setup_proxy, ProxyResponseEvent};
-use crate::bot_runner::{BotProcessStatus, StartBotProcessesEvent, bot_process_system};
-use crate::ui::{camera_controls, setup_camera, ui_system, AppState, CameraPanState, DockerStatus, status_bar_system, GameConfigPanel, GameCreated, build_create_game_request, PendingCreateGameRequest};
+use crate::controller::{response_controller_system, setup_proxy, ProxyActionResultEvent, ProxyRequestEvent, ProxyResponseEvent, PlayerPerspective, ProxyGameEndedEvent, ProxyListenAddr, ProxyRunning};
+use crate::bot_runner::{
+    BotControlEvent, BotOutputBuffer, BotProcessStatus, StartBotProcessesEvent, bot_control_system, bot_lifecycle_system,
+    bot_output_system, bot_process_system, kill_bots_on_exit_system,
+};
+use crate::ui::bot_console::{BotConsoleState, bot_console_system};
+use crate::ui::{camera_controls, setup_camera, ui_system, AppState, CameraPanState, DockerStatus, status_bar_system, GameConfigPanel, GameCreated, build_create_game_request, PendingCreateGameRequest, CreateGameFailedEvent, game_end_system, pending_create_game_system};
+use crate::game_summary::{GameSummaryTracker, game_summary_system, write_game_summary_system};
+use bevy::app::ScheduleRunnerPlugin;
+use std::time::Duration;
 use crate::units::{UnitRegistry, SelectedUnit, unit_selection_system, UnitHealth, UnitShield, UnitBuildProgress, ObservationUnitTags, cleanup_dead_units};
-use crate::units::draw_unit_orders;
+use crate::units::{draw_unit_orders, update_unit_alliance_colors};
 use futures_util::StreamExt;
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
 use std::process::exit;
 use bevy::color::palettes::basic::{GREEN, RED};
-use sc2_proto::common::Race;
 use crate::ui::GameType;
-use crate::app_settings::{AppSettings, load_settings, StarcraftConfig};
+use crate::app_settings::{AppSettings, GameConfigPanelDefaults, load_settings, StarcraftConfig};
 use crate::entity_system::setup_entity_system;
-use crate::ui::game_config_panel::list_maps_folder;
+use crate::ui::game_config_panel::{list_maps_folder, parse_ai_build, parse_difficulty, parse_race};
+use crate::replay::{ReplayPlayback, replay_playback_system, replay_timeline_system};
+use crate::series::{SeriesConfig, SeriesRunner, series_system, series_panel_system};
+use crate::ladder::{LadderRunner, ladder_system, ladder_panel_system};
+use crate::matches::{MatchRecorder, RecentMatches, recent_matches_panel_system};
+use crate::action_errors::{ActionErrorLog, action_error_flash_system, action_error_system, action_errors_panel_system};
+use crate::bot_actions::{BotActionOverlay, bot_action_capture_system, bot_action_draw_system, overlays_panel_system};
+use crate::debug_draw::{DebugDrawOverlay, debug_draw_capture_system, debug_draw_gizmos_system, debug_draw_screen_text_system};
+use crate::step_control::{StepControl, StepControlPanel, step_control_panel_system};
+use crate::debug_commands::{DebugCommandQueue, DebugPanelState, debug_panel_system};
+use crate::scenario::{ScenarioState, scenario_system};
+use crate::assertions::{ExpectationRun, ExpectationRunner, TestSpec, expectation_system};
+use crate::mock_sc2::MockSc2Server;
+use crate::inspector::{ProtocolLog, ProtocolMessageBuffer, protocol_inspector_system, protocol_log_system};
+use crate::latency::{LatencyBuffer, LatencyStats, latency_panel_system, latency_system};
+use crate::bot_resources::{BotResourceMonitor, bot_resource_sampling_system, bot_resources_panel_system};
+use std::path::PathBuf;
 
 fn parse_game_type(mode: &str) -> Option<GameType> {
     match mode.to_lowercase().as_str() {
@@ -42,14 +81,71 @@ fn parse_game_type(mode: &str) -> Option<GameType> {
     }
 }
 
-fn parse_race(race: &str) -> Option<Race> {
-    match race.to_lowercase().as_str() {
-        "terran" => Some(Race::Terran),
-        "zerg" => Some(Race::Zerg),
-        "protoss" => Some(Race::Protoss),
-        "random" => Some(Race::Random),
-        _ => None,
+/// Override the panel fields given on the command line
+fn apply_create_game_args(args: CreateGameArgs, panel: &mut GameConfigPanel) -> Result<(), String> {
+    if let Some(mode) = args.mode {
+        panel.game_type = parse_game_type(&mode)
+            .ok_or_else(|| format!("Invalid mode '{mode}'. Allowed modes: vsAI, vsBot"))?;
+    }
+    if let Some(map) = args.map {
+        if !panel.available_maps.is_empty() && !panel.available_maps.contains(&map) {
+            return Err(format!("Map '{map}' not found in ./maps"));
+        }
+        panel.map_name = Some(map);
+    }
+    if let Some(player_name) = args.player_name {
+        panel.player_name = player_name;
+    }
+    if let Some(race) = args.race {
+        panel.player_race = parse_race(&race)
+            .ok_or_else(|| format!("Invalid race '{race}'. Allowed races: terran, zerg, protoss, random"))?;
+    }
+    if let Some(race) = args.opponent_race {
+        panel.ai_race = Some(parse_race(&race)
+            .ok_or_else(|| format!("Invalid opponent race '{race}'. Allowed races: terran, zerg, protoss, random"))?);
+    }
+    if let Some(difficulty) = args.difficulty {
+        if parse_difficulty(&difficulty).is_none() {
+            return Err(format!(
+                "Invalid difficulty '{difficulty}'. Allowed difficulties: VeryEasy, Easy, Medium, MediumHard, \
+                 Hard, Harder, VeryHard, CheatVision, CheatMoney, CheatInsane"
+            ));
+        }
+        panel.ai_difficulty = Some(difficulty);
+    }
+    if let Some(build) = args.ai_build {
+        panel.ai_build = Some(parse_ai_build(&build)
+            .ok_or_else(|| format!("Invalid AI build '{build}'. Allowed builds: random, rush, timing, power, macro, air"))?);
+    }
+    if let Some(bot_name) = args.bot_name {
+        panel.bot_name = Some(bot_name);
+    }
+    if let Some(disable_fog) = args.disable_fog {
+        panel.disable_fog = disable_fog;
+    }
+    if let Some(seed) = args.seed {
+        panel.random_seed = Some(seed);
+    }
+    if let Some(realtime) = args.realtime {
+        panel.realtime = realtime;
     }
+    if let Some(command) = args.bot_command {
+        panel.bot_command = command;
+    }
+    if let Some(command) = args.opponent_command {
+        panel.bot_opponent_command = command;
+    }
+    if let Some(scenario) = args.scenario {
+        panel.scenario = Some(scenario);
+    }
+    for (bot, target) in [(args.player_bot, &mut panel.player_bot), (args.opponent_bot, &mut panel.opponent_bot)] {
+        let Some(bot) = bot else { continue; };
+        if !panel.available_bots.iter().any(|b| b.name == bot) {
+            return Err(format!("Bot '{bot}' is not defined in [[bots]] of config.toml"));
+        }
+        *target = Some(bot);
+    }
+    Ok(())
 }
 
 #[derive(Parser)]
@@ -57,18 +153,90 @@ fn parse_race(race: &str) -> Option<Race> {
 struct Cli {
     #[command(subcommand)]
     command: Option<CliCommands>,
+    /// Run the proxy, bots and Docker without a window (for CI boxes and servers)
+    #[arg(long, global = true)]
+    headless: bool,
+    /// Test spec (.toml) evaluated on every observation; exits nonzero with a report if an expectation fails
+    #[arg(long, global = true)]
+    expect: Option<PathBuf>,
 }
 
 #[derive(Subcommand)]
 enum CliCommands {
-    /// Create a new game directly from the command line
-    CreateGame {
-        #[arg(long)]
-        mode: Option<String>,
-        #[arg(long)]
-        race: Option<String>,
-        // Add more options as needed
+    /// Create a new game directly from the command line.
+    /// Options not given fall back to the preset (if any), then to config.toml.
+    CreateGame(CreateGameArgs),
+    /// Run a series of games back to back and collect the results
+    Series {
+        /// Path to a series definition (.toml)
+        file: PathBuf,
     },
+    /// Play the bots of the [ladder] roster against each other and update their ratings
+    Ladder,
+    /// Play back a recorded session trace without Docker or the proxy
+    Replay {
+        /// Path to a .sc2trace file
+        trace: PathBuf,
+    },
+    /// Evaluate a test spec against a recorded session trace, without Docker or a window
+    Check {
+        /// Path to a test spec (.toml)
+        spec: PathBuf,
+        /// Path to a .sc2trace file
+        trace: PathBuf,
+    },
+}
+
+#[derive(Args)]
+struct CreateGameArgs {
+    /// Load a saved game preset (same keys as [game_config_panel] in config.toml)
+    #[arg(long)]
+    config: Option<PathBuf>,
+    /// Game type: vsAI or vsBot
+    #[arg(long)]
+    mode: Option<String>,
+    /// Map file name from ./maps
+    #[arg(long)]
+    map: Option<String>,
+    #[arg(long)]
+    player_name: Option<String>,
+    /// Player race: terran, zerg, protoss or random
+    #[arg(long)]
+    race: Option<String>,
+    /// Opponent race (computer or bot): terran, zerg, protoss or random
+    #[arg(long)]
+    opponent_race: Option<String>,
+    /// Computer difficulty, e.g. Easy, Hard, CheatInsane
+    #[arg(long)]
+    difficulty: Option<String>,
+    /// Computer build: random, rush, timing, power, macro, air
+    #[arg(long)]
+    ai_build: Option<String>,
+    /// Opponent bot name (vsBot)
+    #[arg(long)]
+    bot_name: Option<String>,
+    #[arg(long)]
+    disable_fog: Option<bool>,
+    /// Random seed for the game
+    #[arg(long)]
+    seed: Option<u32>,
+    #[arg(long)]
+    realtime: Option<bool>,
+    /// Bash command to run the player bot
+    #[arg(long)]
+    bot_command: Option<String>,
+    /// Bash command to run the opponent bot (vsBot)
+    #[arg(long)]
+    opponent_command: Option<String>,
+    /// Name of a [[bots]] entry to run as the player bot, instead of --bot-command
+    #[arg(long)]
+    player_bot: Option<String>,
+    /// Name of a [[bots]] entry to run as the opponent bot (vsBot), instead of --opponent-command
+    #[arg(long)]
+    opponent_bot: Option<String>,
+    /// Scenario file (.toml or .json): units, resources and upgrades set up at game start
+    #[arg(long)]
+    scenario: Option<String>,
 }
 
 /// Start the server inside Docker and wait until it's reachable.
@@ -92,15 +260,18 @@ fn start_server_container(docker_config : &StarcraftConfig) -> Result<(), String
         .join("maps");
     let maps_mount = format!("{}:/StarCraftII/Maps", maps_dir.display());
 
+    // Publish the main SC2 port plus one per extra bot client
+    let mut port_args = Vec::new();
+    for port in std::iter::once(docker_config.upstream_port).chain(docker_config.extra_upstream_ports.iter().copied()) {
+        port_args.push("-p".to_string());
+        port_args.push(format!("{}:{}", port, port));
+    }
+
     // Run container detached, auto-remove on stop, bind to localhost
     let status = Command::new("docker")
-        .args([
-            "run", "-d", "--rm", "-it",
-            "--name", &container_name,
-            "-p", format!("{}:{}", docker_config.upstream_port, docker_config.upstream_port).as_str(),
-            "-v", &maps_mount,
-            &image,
-        ])
+        .args(["run", "-d", "--rm", "-it", "--name", &container_name])
+        .args(&port_args)
+        .args(["-v", &maps_mount, &image])
         .status()
         .map_err(|e| format!("Failed to execute docker run: {e}"))?;
 
@@ -114,6 +285,11 @@ fn start_server_container(docker_config : &StarcraftConfig) -> Result<(), String
 
 /// Blocking Docker startup for CLI mode
 fn startup_docker_blocking(config: &StarcraftConfig) -> Result<(), String> {
+    if config.mock {
+        // docker_startup_system starts the mock server on the Bevy runtime
+        println!("[startup_docker_blocking] Mock SC2 server enabled, skipping Docker");
+        return Ok(());
+    }
     println!("[startup_docker_blocking] Starting Docker container...");
     let result = start_server_container(&config);
     match &result {
@@ -132,6 +308,29 @@ fn docker_startup_system(
     docker_status.clone_from(&DockerStatus::Starting);
     // Clone config to own it in the task
     let starcraft_config = docker_config.starcraft.clone();
+    if starcraft_config.mock {
+        runtime.spawn_background_task(|mut ctx| async move {
+            let status = match MockSc2Server::from_config(&starcraft_config) {
+                Ok((server, addrs)) => {
+                    let mut status = DockerStatus::Running;
+                    for addr in addrs {
+                        if let Err(e) = server.clone().spawn(&addr).await {
+                            status = DockerStatus::Error(format!("Mock SC2 server on {addr}: {e}"));
+                        }
+                    }
+                    status
+                }
+                Err(e) => DockerStatus::Error(e),
+            };
+            ctx.run_on_main_thread(move |world| {
+                if let Some(mut status_res) = world.world.get_resource_mut::<DockerStatus>() {
+                    println!("[docker_startup_system] Mock SC2 server status: {:?}", status);
+                    status_res.clone_from(&status);
+                }
+            }).await;
+        });
+        return;
+    }
     runtime.spawn_background_task(|mut ctx| async move {
         // Use spawn_blocking for blocking code
         let result = tokio::task::spawn_blocking(move ||
@@ -162,48 +361,133 @@ fn docker_startup_system(
     });
 }
 
-/// System to start proxy connection when Docker is running
+/// System to start proxy connection when Docker is running.
+/// The proxy keeps listening across games; it is only started again if its task exits.
 fn proxy_connect_on_docker_ready(
     docker_status: Res<DockerStatus>,
-    mut has_connected: Local<bool>,
+    mut proxy_running: ResMut<ProxyRunning>,
     runtime: Res<TokioTasksRuntime>,
     game_created: ResMut<GameCreated>,
     settings: Res<AppSettings>,
+    matches: Res<MatchRecorder>,
+    latency: Res<LatencyBuffer>,
+    messages: Res<ProtocolMessageBuffer>,
+    step_control: Res<StepControl>,
+    debug_queue: Res<DebugCommandQueue>,
 ) {
-    if !*has_connected && *docker_status == DockerStatus::Running && game_created.0 {
-        setup_proxy(runtime, settings);
-        *has_connected = true;
-        // game_created.0 = false;
+    if !proxy_running.0 && *docker_status == DockerStatus::Running && game_created.0 {
+        setup_proxy(runtime, settings, matches, latency, messages, step_control, debug_queue);
+        proxy_running.0 = true;
         println!("Proxy connection started after Docker became ready and game was created");
     }
 }
 
+/// System to quit a headless run once its game is over, unless the next game is created automatically
+fn headless_exit_system(
+    mut events: EventReader<ProxyGameEndedEvent>,
+    game_config_panel: Res<GameConfigPanel>,
+    series: Option<Res<SeriesRunner>>,
+    ladder: Option<Res<LadderRunner>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    let game_ended = events.read().last().is_some();
+    // Series and ladder runs queue their own games and are done once their schedule is
+    let scheduled_run_finished = match (series, ladder) {
+        (Some(series), _) => Some(series.is_finished()),
+        (_, Some(ladder)) => Some(ladder.is_finished()),
+        _ => None,
+    };
+    match scheduled_run_finished {
+        Some(true) => {
+            println!("[headless] Scheduled games finished, exiting");
+            exit.write(AppExit::Success);
+        }
+        Some(false) => {}
+        None if game_ended && !game_config_panel.auto_restart => {
+            println!("[headless] Game finished, exiting");
+            exit.write(AppExit::Success);
+        }
+        None => {}
+    }
+}
+
 /// Entry point
 fn main() {
-    let app_settings = load_settings();
+    let mut app_settings = load_settings();
+    let cli = Cli::parse();
+    let headless = cli.headless;
+
+    // Nobody can look at the inspector without a window, so don't copy every message for it
+    if headless && app_settings.inspector.enabled {
+        println!("[headless] Protocol inspector disabled");
+        app_settings.inspector.enabled = false;
+    }
+
+    // Only the start screen picks up the last created game; CLI and headless runs follow config.toml
+    let panel_defaults = if !headless && cli.command.is_none() {
+        app_settings.game_config_panel.clone().with_last_game()
+    } else {
+        app_settings.game_config_panel.clone()
+    };
     let available_maps = list_maps_folder();
-    let mut game_config_panel = GameConfigPanel::from_defaults(&app_settings.game_config_panel, available_maps);
+    let mut game_config_panel = GameConfigPanel::from_defaults(&panel_defaults, available_maps);
+    game_config_panel.available_bots = app_settings.bots.clone();
 
-    let cli = Cli::parse();
+    let expectations = cli.expect.as_ref().map(|path| {
+        TestSpec::load(path).and_then(ExpectationRun::new).unwrap_or_else(|e| {
+            eprintln!("Error: {e}");
+            exit(1);
+        })
+    });
+
+    if let Some(CliCommands::Check { spec, trace }) = &cli.command {
+        let run = TestSpec::load(spec).and_then(ExpectationRun::new);
+        let playback = ReplayPlayback::load(trace);
+        let (mut run, playback) = match (run, playback) {
+            (Ok(run), Ok(playback)) => (run, playback),
+            (Err(e), _) | (_, Err(e)) => {
+                eprintln!("Error: {e}");
+                exit(1);
+            }
+        };
+        for frame in &playback.frames {
+            run.observe(playback.player_id, frame.response.get_observation());
+        }
+        exit(if run.report() { 0 } else { 1 });
+    }
 
     // Default values for resources
     let mut app_state = AppState::StartScreen;
     let mut pending_request = PendingCreateGameRequest::default();
+    let mut replay = None;
 
-    if let Some(CliCommands::CreateGame { mode, race }) = cli.command {
-        // Check required params
-        if mode.is_none() || race.is_none() {
-            eprintln!("Error: --mode and --race are required for create_game\n");
-            eprintln!("Usage: aiurgaze create_game --mode=<MODE> --race=<RACE>");
+    if let Some(CliCommands::Replay { trace }) = &cli.command {
+        if headless {
+            eprintln!("Error: replay needs a window and can't run with --headless");
             exit(1);
         }
-        let mode_val = mode.unwrap();
-        let race_val = race.unwrap();
-        let game_type = parse_game_type(&mode_val);
-        let race_enum = parse_race(&race_val);
-        if game_type.is_none() || race_enum.is_none() {
-            eprintln!("Error: Invalid mode or race value\n");
-            eprintln!("Allowed modes: vsAI, vsBot\nAllowed races: terran, zerg, protoss, random");
+        match ReplayPlayback::load(trace) {
+            Ok(playback) => replay = Some(playback),
+            Err(e) => {
+                eprintln!("Error: {e}");
+                exit(1);
+            }
+        }
+        app_state = AppState::GameScreen;
+    }
+
+    if let Some(CliCommands::CreateGame(mut args)) = cli.command {
+        if let Some(preset_path) = args.config.take() {
+            let preset = GameConfigPanelDefaults::load_preset(&preset_path).unwrap_or_else(|e| {
+                eprintln!("Error: {e}");
+                exit(1);
+            });
+            let defaults = app_settings.game_config_panel.clone().overridden_by(preset);
+            game_config_panel = GameConfigPanel::from_defaults(&defaults, list_maps_folder());
+            game_config_panel.available_bots = app_settings.bots.clone();
+        }
+        if let Err(e) = apply_create_game_args(args, &mut game_config_panel) {
+            eprintln!("Error: {e}");
             exit(1);
         }
         // Start Docker synchronously in CLI mode
@@ -213,13 +497,11 @@ fn main() {
         }
         // Set up resources to skip the start screen
         app_state = AppState::GameScreen;
-        game_config_panel.game_type = game_type.unwrap();
-        game_config_panel.ai_race = Some(race_enum.unwrap());
 
-        // Build the request and store it in the resource to be sent by ui_system
+        // Build the request and store it in the resource to be sent by pending_create_game_system
         match build_create_game_request(&game_config_panel) {
             Ok(req) => {
-                println!("[CLI] CreateGame request built, will be sent by ui_system within Bevy");
+                println!("[CLI] CreateGame request built, will be sent by pending_create_game_system within Bevy");
                 pending_request.0 = Some(req);
             }
             Err(e) => {
@@ -229,68 +511,210 @@ fn main() {
         }
     }
 
-    App::new()
+    let mut series = None;
+    if let Some(CliCommands::Series { file }) = &cli.command {
+        let config = SeriesConfig::load(file).unwrap_or_else(|e| {
+            eprintln!("Error: {e}");
+            exit(1);
+        });
+        if let Err(e) = startup_docker_blocking(&app_settings.starcraft) {
+            eprintln!("Error: Could not start Docker container: {e}");
+            exit(1);
+        }
+        // The series queues its own games, one after the other
+        app_state = AppState::GameScreen;
+        series = Some(SeriesRunner::new(config));
+    }
+
+    let mut ladder = None;
+    if let Some(CliCommands::Ladder) = &cli.command {
+        let runner = LadderRunner::new(app_settings.ladder.clone()).unwrap_or_else(|e| {
+            eprintln!("Error: {e}");
+            exit(1);
+        });
+        if let Err(e) = startup_docker_blocking(&app_settings.starcraft) {
+            eprintln!("Error: Could not start Docker container: {e}");
+            exit(1);
+        }
+        app_state = AppState::GameScreen;
+        ladder = Some(runner);
+    }
+
+    // Without a window there is no "Create Game" button: create one from the config defaults
+    if headless && pending_request.0.is_none() && series.is_none() && ladder.is_none() {
+        match build_create_game_request(&game_config_panel) {
+            Ok(req) => pending_request.0 = Some(req),
+            Err(e) => {
+                eprintln!("Error: Failed to build create game request: {}", e);
+                exit(1);
+            }
+        }
+    }
+
+    let is_replay = replay.is_some();
+    let window_config = app_settings.window.clone();
+    let match_recorder = MatchRecorder::new(&app_settings);
+    let bot_action_overlay = BotActionOverlay::new(&app_settings);
+    let debug_draw_overlay = DebugDrawOverlay::new(&app_settings);
+    let mut app = App::new();
+    app
         .add_event::<ProxyResponseEvent>()
+        .add_event::<ProxyRequestEvent>()
+        .add_event::<ProxyActionResultEvent>()
         .add_event::<StartBotProcessesEvent>()
-        .register_type::<UnitHealth>()
-        .register_type::<UnitShield>()
-        .register_type::<UnitBuildProgress>()
-        .add_plugins(
-            DefaultPlugins
-                .set(ImagePlugin::default_nearest())
-                .set(WindowPlugin {
-                    primary_window: Some(Window {
-                        title: "aiurgaze - SC2 AI Observer".to_string(),
-                        resolution: (app_settings.window.width, app_settings.window.height).into(),
-                        resizable: app_settings.window.resizable,
-                        ..default()
-                    }),
-                    ..default()
-                })
-        )
-        .add_plugins(TilemapPlugin)
-        .add_plugins(EguiPlugin::default())
+        .add_event::<BotControlEvent>()
+        .add_event::<ProxyGameEndedEvent>()
+        .add_event::<CreateGameFailedEvent>()
         .add_plugins(TokioTasksPlugin::default())
-        .add_plugins(HealthBarPlugin::<UnitHealth>::default())
-        .add_plugins(HealthBarPlugin::<UnitShield>::default())
-        .add_plugins(HealthBarPlugin::<UnitBuildProgress>::default())
-        .insert_resource(
-            ColorScheme::<UnitHealth>::new()
-                .foreground_color(ForegroundColor::Static(GREEN.into()))
-                .background_color(RED.into()),
-        )
-        .insert_resource(
-            ColorScheme::<UnitShield>::new()
-                .foreground_color(ForegroundColor::Static(Color::srgb(0.3, 0.6, 1.0)))
-                .background_color(Color::srgb(0.1, 0.1, 0.3)),
-        )
-        .insert_resource(
-            ColorScheme::<UnitBuildProgress>::new()
-                .foreground_color(ForegroundColor::Static(Color::srgb(1.0, 0.9, 0.2)))
-                .background_color(Color::srgb(0.3, 0.3, 0.1)),
-        )
         .insert_resource(GameCreated(false))
-        .insert_resource(UnitRegistry::default())
-        .insert_resource(SelectedUnit::default())
-        .insert_resource(ObservationUnitTags::default())
-        .insert_resource(CameraPanState::default())
+        .insert_resource(ProxyRunning::default())
+        .insert_resource(ProxyListenAddr::default())
+        .insert_resource(PlayerPerspective::default())
         .insert_resource(BotProcessStatus::default())
+        .insert_resource(BotOutputBuffer::default())
+        .insert_resource(GameSummaryTracker::default())
         .insert_resource(game_config_panel)
-        .insert_resource(DockerStatus::Starting)
+        .insert_resource(if is_replay { DockerStatus::Disabled } else { DockerStatus::Starting })
         .insert_resource(pending_request)
+        .insert_resource(match_recorder)
+        .insert_resource(BotResourceMonitor::default())
+        .insert_resource(LatencyBuffer::default())
+        .insert_resource(LatencyStats::default())
+        .insert_resource(ProtocolMessageBuffer::default())
+        .insert_resource(ProtocolLog::default())
+        .insert_resource(StepControl::default())
+        .insert_resource(DebugCommandQueue::default())
+        .insert_resource(ScenarioState::default())
         .insert_resource(app_settings) // use loaded settings
         .insert_resource(app_state)
-        .add_systems(Startup, setup_entity_system)
-        .add_systems(Startup, setup_camera)
-        .add_systems(Update, unit_selection_system)
-        .add_systems(Update, camera_controls)
-        .add_systems(Startup, docker_startup_system)
-        .add_systems(EguiPrimaryContextPass, ui_system)
-        .add_systems(EguiPrimaryContextPass, status_bar_system)
-        .add_systems(Update, response_controller_system)
-        .add_systems(Update, cleanup_dead_units.after(response_controller_system))
-        .add_systems(Update, proxy_connect_on_docker_ready)
+        .add_systems(Update, pending_create_game_system)
         .add_systems(Update, bot_process_system)
-        .add_systems(Update, draw_unit_orders)
-        .run();
+        .add_systems(Update, bot_output_system)
+        .add_systems(Update, bot_control_system)
+        .add_systems(Update, bot_lifecycle_system.before(bot_process_system))
+        .add_systems(Update, bot_resource_sampling_system)
+        .add_systems(Update, protocol_log_system)
+        .add_systems(Update, scenario_system)
+        // Exports into the match directory, which the game summary closes
+        .add_systems(Update, latency_system.before(write_game_summary_system))
+        .add_systems(Last, kill_bots_on_exit_system)
+        .add_systems(Update, game_summary_system)
+        // Summaries close their match before an automatic restart opens the next one
+        .add_systems(Update, write_game_summary_system.after(game_summary_system).before(pending_create_game_system));
+
+    if headless {
+        // No window, egui, tilemap or sprites: just tick the schedule at a steady rate
+        app.add_plugins(MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(Duration::from_secs_f64(1.0 / 60.0))))
+            .add_systems(Update, headless_exit_system);
+    } else {
+        app.register_type::<UnitHealth>()
+            .register_type::<UnitShield>()
+            .register_type::<UnitBuildProgress>()
+            .add_plugins(
+                DefaultPlugins
+                    .set(ImagePlugin::default_nearest())
+                    .set(WindowPlugin {
+                        primary_window: Some(Window {
+                            title: "aiurgaze - SC2 AI Observer".to_string(),
+                            resolution: (window_config.width, window_config.height).into(),
+                            resizable: window_config.resizable,
+                            ..default()
+                        }),
+                        ..default()
+                    })
+            )
+            .add_plugins(TilemapPlugin)
+            .add_plugins(EguiPlugin::default())
+            .add_plugins(HealthBarPlugin::<UnitHealth>::default())
+            .add_plugins(HealthBarPlugin::<UnitShield>::default())
+            .add_plugins(HealthBarPlugin::<UnitBuildProgress>::default())
+            .insert_resource(
+                ColorScheme::<UnitHealth>::new()
+                    .foreground_color(ForegroundColor::Static(GREEN.into()))
+                    .background_color(RED.into()),
+            )
+            .insert_resource(
+                ColorScheme::<UnitShield>::new()
+                    .foreground_color(ForegroundColor::Static(Color::srgb(0.3, 0.6, 1.0)))
+                    .background_color(Color::srgb(0.1, 0.1, 0.3)),
+            )
+            .insert_resource(
+                ColorScheme::<UnitBuildProgress>::new()
+                    .foreground_color(ForegroundColor::Static(Color::srgb(1.0, 0.9, 0.2)))
+                    .background_color(Color::srgb(0.3, 0.3, 0.1)),
+            )
+            .insert_resource(UnitRegistry::default())
+            .insert_resource(SelectedUnit::default())
+            .insert_resource(ObservationUnitTags::default())
+            .insert_resource(CameraPanState::default())
+            .insert_resource(BotConsoleState::default())
+            .insert_resource(RecentMatches::default())
+            .insert_resource(ActionErrorLog::default())
+            .insert_resource(bot_action_overlay)
+            .insert_resource(debug_draw_overlay)
+            .insert_resource(StepControlPanel::default())
+            .insert_resource(DebugPanelState::default())
+            .add_systems(Startup, setup_entity_system)
+            .add_systems(Startup, setup_camera)
+            .add_systems(Update, unit_selection_system)
+            .add_systems(Update, camera_controls)
+            .add_systems(EguiPrimaryContextPass, ui_system)
+            .add_systems(EguiPrimaryContextPass, recent_matches_panel_system.before(ui_system))
+            .add_systems(EguiPrimaryContextPass, status_bar_system)
+            .add_systems(EguiPrimaryContextPass, bot_console_system.after(status_bar_system))
+            .add_systems(EguiPrimaryContextPass, bot_resources_panel_system)
+            .add_systems(EguiPrimaryContextPass, latency_panel_system)
+            .add_systems(EguiPrimaryContextPass, protocol_inspector_system)
+            .add_systems(EguiPrimaryContextPass, action_errors_panel_system)
+            .add_systems(EguiPrimaryContextPass, overlays_panel_system)
+            .add_systems(EguiPrimaryContextPass, debug_draw_screen_text_system)
+            .add_systems(EguiPrimaryContextPass, step_control_panel_system)
+            .add_systems(EguiPrimaryContextPass, debug_panel_system)
+            .add_systems(Update, response_controller_system)
+            .add_systems(Update, cleanup_dead_units.after(response_controller_system))
+            .add_systems(Update, draw_unit_orders)
+            .add_systems(Update, update_unit_alliance_colors.after(response_controller_system))
+            .add_systems(Update, action_error_system.after(response_controller_system))
+            .add_systems(Update, action_error_flash_system.after(update_unit_alliance_colors))
+            .add_systems(Update, bot_action_capture_system.after(response_controller_system))
+            .add_systems(Update, bot_action_draw_system.after(bot_action_capture_system))
+            .add_systems(Update, debug_draw_capture_system.after(response_controller_system))
+            .add_systems(Update, debug_draw_gizmos_system.after(debug_draw_capture_system));
+    }
+
+    if let Some(runner) = series {
+        app.insert_resource(runner)
+            .add_systems(Update, series_system.before(pending_create_game_system));
+        if !headless {
+            app.add_systems(EguiPrimaryContextPass, series_panel_system);
+        }
+    }
+
+    if let Some(runner) = ladder {
+        app.insert_resource(runner)
+            .add_systems(Update, ladder_system.before(pending_create_game_system));
+        if !headless {
+            app.add_systems(EguiPrimaryContextPass, ladder_panel_system);
+        }
+    }
+
+    if let Some(run) = expectations {
+        app.insert_resource(ExpectationRunner(run))
+            .add_systems(Update, expectation_system);
+    }
+
+    if let Some(playback) = replay {
+        // Offline playback: recorded responses replace Docker and the proxy
+        app.insert_resource(playback)
+            .add_systems(Update, replay_playback_system.before(response_controller_system))
+            .add_systems(EguiPrimaryContextPass, replay_timeline_system.before(status_bar_system));
+    } else {
+        app.add_systems(Startup, docker_startup_system)
+            .add_systems(Update, proxy_connect_on_docker_ready)
+            .add_systems(Update, game_end_system);
+    }
+
+    if let AppExit::Error(code) = app.run() {
+        exit(code.get() as i32);
+    }
 }
@@ -0,0 +1,273 @@
+// src/matches.rs
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use protobuf::Message;
+use sc2_proto::sc2api::Request;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use crate::app_settings::AppSettings;
+use crate::game_summary::GameSummary;
+use crate::trace::{unix_millis, TRACE_EXTENSION};
+use crate::ui::{AppState, GameConfigPanel, GameType};
+
+/// File names inside a match directory
+pub const CREATE_GAME_TEXT: &str = "create_game.txt";
+pub const CREATE_GAME_BINARY: &str = "create_game.bin";
+pub const SUMMARY_FILE: &str = "summary.json";
+
+/// Resource owning the directory of the match in progress.
+/// The proxy reads `trace_dir` whenever a client connects, so session traces land next to the bot logs.
+#[derive(Resource)]
+pub struct MatchRecorder {
+    pub current: Option<PathBuf>,
+    trace_dir: Arc<Mutex<Option<PathBuf>>>,
+    /// Where traces go when no match directory is open, e.g. bots joining a game created elsewhere
+    default_trace_dir: Option<PathBuf>,
+    record_traces: bool,
+}
+
+impl MatchRecorder {
+    pub fn new(settings: &AppSettings) -> Self {
+        let default_trace_dir = settings.trace.enabled.then(|| PathBuf::from(&settings.trace.dir));
+        Self {
+            current: None,
+            trace_dir: Arc::new(Mutex::new(default_trace_dir.clone())),
+            default_trace_dir,
+            record_traces: settings.trace.enabled,
+        }
+    }
+
+    /// Handle handed to the proxy to pick the trace directory of each session
+    pub fn trace_dir_handle(&self) -> Arc<Mutex<Option<PathBuf>>> {
+        self.trace_dir.clone()
+    }
+
+    /// Open the directory of a new match and store the CreateGame request that starts it
+    pub fn begin(&mut self, settings: &AppSettings, panel: &GameConfigPanel, request: &Request) {
+        if !settings.matches.enabled {
+            return;
+        }
+        let dir = Path::new(&settings.matches.dir).join(match_dir_name(panel, unix_millis()));
+        if let Err(e) = fs::create_dir_all(&dir) {
+            eprintln!("[matches] Failed to create {}: {}", dir.display(), e);
+            return;
+        }
+        let text = protobuf::text_format::print_to_string(request);
+        if let Err(e) = fs::write(dir.join(CREATE_GAME_TEXT), text) {
+            eprintln!("[matches] Failed to write {}: {}", CREATE_GAME_TEXT, e);
+        }
+        match request.write_to_bytes() {
+            Ok(bytes) => {
+                if let Err(e) = fs::write(dir.join(CREATE_GAME_BINARY), bytes) {
+                    eprintln!("[matches] Failed to write {}: {}", CREATE_GAME_BINARY, e);
+                }
+            }
+            Err(e) => eprintln!("[matches] Failed to encode the CreateGame request: {}", e),
+        }
+
+        println!("[matches] Recording match to {}", dir.display());
+        if self.record_traces {
+            *self.trace_dir.lock().unwrap() = Some(dir.clone());
+        }
+        self.current = Some(dir);
+    }
+
+    /// Close the current match; later sessions are traced to the default directory again
+    pub fn finish(&mut self) -> Option<PathBuf> {
+        *self.trace_dir.lock().unwrap() = self.default_trace_dir.clone();
+        self.current.take()
+    }
+}
+
+/// `<date>-<time>_<map>_<player>_vs_<opponent>`, with characters unsafe in file names replaced
+fn match_dir_name(panel: &GameConfigPanel, now_ms: u64) -> String {
+    let map = panel.map_name.as_deref().unwrap_or("unknown").trim_end_matches(".SC2Map");
+    let opponent = match panel.game_type {
+        GameType::VsAI => format!("AI-{}", panel.ai_difficulty.as_deref().unwrap_or("Medium")),
+        GameType::VsBot => panel.bot_name.clone().filter(|n| !n.is_empty()).unwrap_or_else(|| "Bot".to_string()),
+    };
+    let name = format!("{}_{}_{}_vs_{}", utc_timestamp(now_ms), map, panel.player_name, opponent);
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') { c } else { '-' })
+        .collect()
+}
+
+/// Format milliseconds since the Unix epoch as `YYYYMMDD-HHMMSS` in UTC
+pub fn utc_timestamp(ms: u64) -> String {
+    let secs = ms / 1000;
+    let (days, rem) = ((secs / 86_400) as i64, secs % 86_400);
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}",
+        year, month, day, rem / 3600, rem % 3600 / 60, rem % 60
+    )
+}
+
+/// A finished or interrupted match found on disk
+#[derive(Debug, Clone)]
+pub struct MatchEntry {
+    pub name: String,
+    pub path: PathBuf,
+    pub summary: Option<GameSummary>,
+    pub traces: Vec<PathBuf>,
+    pub logs: Vec<PathBuf>,
+}
+
+impl MatchEntry {
+    fn load(path: PathBuf) -> Option<Self> {
+        let name = path.file_name()?.to_string_lossy().into_owned();
+        let summary = fs::read_to_string(path.join(SUMMARY_FILE)).ok()
+            .and_then(|content| serde_json::from_str(&content).ok());
+        let mut traces = Vec::new();
+        let mut logs = Vec::new();
+        for entry in fs::read_dir(&path).ok()?.filter_map(|e| e.ok()) {
+            let file = entry.path();
+            match file.extension().and_then(|e| e.to_str()) {
+                Some(ext) if ext == TRACE_EXTENSION => traces.push(file),
+                Some("log") => logs.push(file),
+                _ => {}
+            }
+        }
+        traces.sort();
+        logs.sort();
+        Some(Self { name, path, summary, traces, logs })
+    }
+}
+
+/// Match directories under `dir`, newest first
+pub fn list_matches(dir: &Path, limit: usize) -> Vec<MatchEntry> {
+    let Ok(entries) = fs::read_dir(dir) else { return Vec::new(); };
+    let mut dirs: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.is_dir())
+        .collect();
+    // Names start with the timestamp, so they sort chronologically
+    dirs.sort();
+    dirs.into_iter().rev().take(limit).filter_map(MatchEntry::load).collect()
+}
+
+/// Matches shown on the start screen; reloaded when the start screen is entered
+#[derive(Resource, Default)]
+pub struct RecentMatches {
+    pub entries: Vec<MatchEntry>,
+    loaded: bool,
+}
+
+/// Side panel on the start screen listing recent matches, with their results and files
+pub fn recent_matches_panel_system(
+    mut contexts: EguiContexts,
+    app_state: Res<AppState>,
+    settings: Res<AppSettings>,
+    mut recent: ResMut<RecentMatches>,
+) {
+    if *app_state != AppState::StartScreen {
+        recent.loaded = false;
+        return;
+    }
+    if !recent.loaded {
+        recent.entries = list_matches(Path::new(&settings.matches.dir), settings.matches.recent);
+        recent.loaded = true;
+    }
+    let Ok(ctx) = contexts.ctx_mut() else { return; };
+
+    egui::SidePanel::left("recent_matches_panel")
+        .resizable(true)
+        .default_width(320.0)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.heading("Recent matches");
+                if ui.button("Refresh").clicked() {
+                    recent.loaded = false;
+                }
+            });
+            ui.separator();
+            if recent.entries.is_empty() {
+                ui.label(format!("No matches in ./{}", settings.matches.dir));
+                return;
+            }
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                for entry in &recent.entries {
+                    let result = entry.summary.as_ref()
+                        .and_then(|s| s.results.iter().find(|r| r.player_id == 1))
+                        .map(|r| r.result.as_str())
+                        .unwrap_or("no result");
+                    egui::CollapsingHeader::new(format!("{} ({})", entry.name, result))
+                        .id_salt(&entry.path)
+                        .show(ui, |ui| show_match_entry(ui, entry));
+                }
+            });
+        });
+}
+
+fn show_match_entry(ui: &mut egui::Ui, entry: &MatchEntry) {
+    if let Some(summary) = &entry.summary {
+        ui.label(format!("Map: {}", summary.map_name));
+        for player in &summary.players {
+            let result = summary.results.iter()
+                .find(|r| r.player_id == player.player_id)
+                .map(|r| r.result.as_str())
+                .unwrap_or("-");
+            ui.label(format!("Player {}: {} ({}, {}) - {}", player.player_id, player.name, player.player_type, player.race, result));
+        }
+        ui.label(format!("Game loops: {}", summary.last_game_loop));
+    }
+    ui.horizontal(|ui| {
+        if ui.button("Copy path").clicked() {
+            ui.ctx().copy_text(entry.path.display().to_string());
+        }
+    });
+    for trace in &entry.traces {
+        ui.horizontal(|ui| {
+            ui.label(trace.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default());
+            if ui.button("Replay").clicked() {
+                open_replay(trace);
+            }
+        });
+    }
+    for log in &entry.logs {
+        ui.label(log.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default());
+    }
+}
+
+/// Open a trace in a separate aiurgaze window, leaving this one free for the next game
+fn open_replay(trace: &Path) {
+    let result = std::env::current_exe()
+        .and_then(|exe| std::process::Command::new(exe).arg("replay").arg(trace).spawn());
+    if let Err(e) = result {
+        eprintln!("[matches] Failed to open replay of {}: {}", trace.display(), e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn utc_timestamp_of_the_epoch() {
+        assert_eq!(utc_timestamp(0), "19700101-000000");
+    }
+
+    #[test]
+    fn utc_timestamp_of_leap_days() {
+        assert_eq!(utc_timestamp(951_782_400_000), "20000229-000000");
+        // 2100 is not a leap year
+        assert_eq!(utc_timestamp(4_107_542_400_000), "21000301-000000");
+    }
+
+    #[test]
+    fn utc_timestamp_of_recent_dates() {
+        assert_eq!(utc_timestamp(1_710_506_096_789), "20240315-123456");
+        assert_eq!(utc_timestamp(1_704_067_199_000), "20231231-235959");
+    }
+}
@@ -0,0 +1,472 @@
+// src/mock_sc2.rs
+use futures_util::{SinkExt, StreamExt};
+use protobuf::{Message, RepeatedField};
+use sc2_proto::common::{ImageData, Point2D, PointI, Race, RectangleI, Size2DI};
+use sc2_proto::error::ActionResult;
+use sc2_proto::sc2api::{
+    PlayerInfo, PlayerResult, PlayerType, Request, Request_oneof_request, Response, ResponseGameInfo,
+    ResponseObservation, Result as GameResult, Status,
+};
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_tungstenite::accept_async;
+use tokio_tungstenite::tungstenite::Message as WsMessage;
+use crate::app_settings::StarcraftConfig;
+use crate::replay::ReplayPlayback;
+
+/// Size of the synthetic map when no trace is replayed
+const SYNTHETIC_MAP_SIZE: (i32, i32) = (64, 64);
+/// Unpathable cells around the synthetic map
+const SYNTHETIC_BORDER: i32 = 4;
+/// Game length without canned observations, one minute at "faster" speed
+const SYNTHETIC_END_LOOP: u32 = 1344;
+
+/// Game the mock server plays: its game_info and the observations replayed as clients step
+pub struct MockGame {
+    pub game_info: ResponseGameInfo,
+    /// Ordered by game loop; a client gets the last one at or before its current loop
+    pub observations: Vec<ResponseObservation>,
+    /// Loop at which the game ends: player 1 wins, every other player loses
+    pub end_loop: u32,
+}
+
+impl MockGame {
+    /// Flat map with an unpathable border and no units
+    pub fn synthetic() -> Self {
+        let (w, h) = SYNTHETIC_MAP_SIZE;
+        let inside = |x: i32, y: i32| {
+            x >= SYNTHETIC_BORDER && y >= SYNTHETIC_BORDER && x < w - SYNTHETIC_BORDER && y < h - SYNTHETIC_BORDER
+        };
+        let cells: Vec<bool> = (0..h).flat_map(|y| (0..w).map(move |x| inside(x, y))).collect();
+
+        let mut game_info = ResponseGameInfo::new();
+        game_info.set_map_name("Mock".to_string());
+        game_info.set_local_map_path("Mock.SC2Map".to_string());
+        let start_raw = game_info.mut_start_raw();
+        start_raw.set_map_size(size(w, h));
+        start_raw.set_pathing_grid(image(w, h, 1, pack_bits(&cells)));
+        start_raw.set_placement_grid(image(w, h, 1, pack_bits(&cells)));
+        start_raw.set_terrain_height(image(w, h, 8, cells.iter().map(|&c| if c { 160 } else { 96 }).collect()));
+        let mut playable = RectangleI::new();
+        playable.set_p0(point(SYNTHETIC_BORDER, SYNTHETIC_BORDER));
+        playable.set_p1(point(w - SYNTHETIC_BORDER, h - SYNTHETIC_BORDER));
+        start_raw.set_playable_area(playable);
+        // The viewer centres on the first start location, so there has to be one
+        let mut start = Point2D::new();
+        start.set_x(w as f32 / 2.0);
+        start.set_y(h as f32 / 2.0);
+        start_raw.mut_start_locations().push(start);
+
+        Self { game_info, observations: Vec::new(), end_loop: SYNTHETIC_END_LOOP }
+    }
+
+    /// Replay the game_info and observations of a recorded session trace.
+    /// Every client gets the recorded player's view, units and alliances unchanged, so this is meant for one client.
+    pub fn from_trace(path: &Path) -> Result<Self, String> {
+        let playback = ReplayPlayback::load(path)?;
+        let end_loop = playback.last_loop();
+        Ok(Self {
+            game_info: playback.game_info.get_game_info().clone(),
+            observations: playback.frames.into_iter().map(|f| f.response.get_observation().clone()).collect(),
+            end_loop,
+        })
+    }
+}
+
+fn size(x: i32, y: i32) -> Size2DI {
+    let mut size = Size2DI::new();
+    size.set_x(x);
+    size.set_y(y);
+    size
+}
+
+fn point(x: i32, y: i32) -> PointI {
+    let mut point = PointI::new();
+    point.set_x(x);
+    point.set_y(y);
+    point
+}
+
+fn image(w: i32, h: i32, bits_per_pixel: i32, data: Vec<u8>) -> ImageData {
+    let mut image = ImageData::new();
+    image.set_bits_per_pixel(bits_per_pixel);
+    image.set_size(size(w, h));
+    image.set_data(data);
+    image
+}
+
+/// Most significant bit first, as SC2 packs 1-bit grids
+fn pack_bits(cells: &[bool]) -> Vec<u8> {
+    cells.chunks(8)
+        .map(|chunk| chunk.iter().enumerate().fold(0u8, |byte, (i, &set)| byte | ((set as u8) << (7 - i))))
+        .collect()
+}
+
+#[derive(Default)]
+struct MockState {
+    created: bool,
+    /// Player IDs handed out so far
+    joined: u32,
+}
+
+/// In-process stand-in for the SC2 websocket API: enough of it to create and join a game,
+/// draw its map and step through canned observations, without Docker or the game
+#[derive(Clone)]
+pub struct MockSc2Server {
+    game: Arc<MockGame>,
+    /// Shared by every port, so clients on different ports join the same game
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockSc2Server {
+    pub fn new(game: MockGame) -> Self {
+        Self { game: Arc::new(game), state: Arc::new(Mutex::new(MockState::default())) }
+    }
+
+    /// Mock server for the `[starcraft]` settings: replays `mock_trace` if set, on every upstream port
+    pub fn from_config(config: &StarcraftConfig) -> Result<(Self, Vec<String>), String> {
+        let game = match config.mock_trace.as_deref().filter(|p| !p.is_empty()) {
+            Some(path) => MockGame::from_trace(Path::new(path))?,
+            None => MockGame::synthetic(),
+        };
+        let host = config.upstream_url.trim_start_matches("ws://");
+        let addrs = std::iter::once(config.upstream_port)
+            .chain(config.extra_upstream_ports.iter().copied())
+            .map(|port| format!("{}:{}", host, port))
+            .collect();
+        Ok((Self::new(game), addrs))
+    }
+
+    /// Bind a listener, e.g. on port 0 to let the OS pick one, and serve it in the background
+    pub async fn spawn(self, addr: &str) -> std::io::Result<SocketAddr> {
+        let listener = TcpListener::bind(addr).await?;
+        let local_addr = listener.local_addr()?;
+        println!("[mock_sc2] Listening on ws://{}/sc2api", local_addr);
+        tokio::spawn(self.serve(listener));
+        Ok(local_addr)
+    }
+
+    async fn serve(self, listener: TcpListener) {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    tokio::spawn(self.clone().handle_connection(stream));
+                }
+                Err(e) => eprintln!("[mock_sc2] Accept failed: {}", e),
+            }
+        }
+    }
+
+    async fn handle_connection(self, stream: TcpStream) {
+        let mut ws = match accept_async(stream).await {
+            Ok(ws) => ws,
+            Err(e) => {
+                eprintln!("[mock_sc2] Handshake failed: {}", e);
+                return;
+            }
+        };
+        let mut client = MockClient::default();
+        while let Some(Ok(msg)) = ws.next().await {
+            let WsMessage::Binary(bytes) = msg else { continue; };
+            let Ok(request) = Request::parse_from_bytes(&bytes) else {
+                eprintln!("[mock_sc2] Ignoring a message that is not a Request");
+                continue;
+            };
+            let (response, quit) = self.respond(&mut client, request);
+            let Ok(bytes) = response.write_to_bytes() else { break; };
+            if ws.send(WsMessage::binary(bytes)).await.is_err() || quit {
+                break;
+            }
+        }
+    }
+
+    fn respond(&self, client: &mut MockClient, request: Request) -> (Response, bool) {
+        let mut response = Response::new();
+        let mut quit = false;
+        match request.request {
+            Some(Request_oneof_request::ping(_)) => {
+                let ping = response.mut_ping();
+                ping.set_game_version("mock".to_string());
+                ping.set_data_version(String::new());
+                ping.set_data_build(0);
+                ping.set_base_build(0);
+            }
+            Some(Request_oneof_request::create_game(create)) => {
+                let mut state = self.state.lock().unwrap();
+                println!("[mock_sc2] Creating game on {}", create.get_local_map().get_map_path());
+                *state = MockState { created: true, joined: 0 };
+                response.mut_create_game();
+            }
+            Some(Request_oneof_request::join_game(join)) => {
+                let mut state = self.state.lock().unwrap();
+                if !state.created {
+                    response.mut_error().push("No game created".to_string());
+                } else {
+                    state.joined += 1;
+                    client.player_id = state.joined;
+                    client.race = join.get_race();
+                    response.mut_join_game().set_player_id(client.player_id);
+                }
+            }
+            Some(Request_oneof_request::game_info(_)) => {
+                let mut game_info = self.game.game_info.clone();
+                if game_info.get_player_info().is_empty() {
+                    let mut player = PlayerInfo::new();
+                    player.set_player_id(client.player_id);
+                    player.set_field_type(PlayerType::Participant);
+                    player.set_race_requested(client.race);
+                    game_info.mut_player_info().push(player);
+                }
+                response.set_game_info(game_info);
+            }
+            Some(Request_oneof_request::observation(_)) => {
+                response.set_observation(self.observation(client));
+            }
+            Some(Request_oneof_request::step(step)) => {
+                client.game_loop += step.get_count().max(1);
+                response.mut_step();
+            }
+            Some(Request_oneof_request::action(action)) => {
+                let results = vec![ActionResult::Success; action.get_actions().len()];
+                response.mut_action().set_result(results);
+            }
+            Some(Request_oneof_request::debug(_)) => {
+                response.mut_debug();
+            }
+            Some(Request_oneof_request::data(_)) => {
+                response.mut_data();
+            }
+            Some(Request_oneof_request::leave_game(_)) => {
+                response.mut_leave_game();
+                quit = true;
+            }
+            Some(Request_oneof_request::quit(_)) => {
+                response.mut_quit();
+                quit = true;
+            }
+            _ => {
+                response.mut_error().push("Request not supported by the mock SC2 server".to_string());
+            }
+        }
+        let ended = client.game_loop >= self.game.end_loop;
+        response.set_status(match (client.player_id, ended) {
+            (0, _) if self.state.lock().unwrap().created => Status::init_game,
+            (0, _) => Status::launched,
+            (_, false) => Status::in_game,
+            (_, true) => Status::ended,
+        });
+        (response, quit)
+    }
+
+    /// Last canned observation due at the client's loop, stamped with that loop
+    fn observation(&self, client: &MockClient) -> ResponseObservation {
+        let index = self.game.observations.partition_point(|o| o.get_observation().get_game_loop() <= client.game_loop);
+        let mut observation = index.checked_sub(1)
+            .and_then(|i| self.game.observations.get(i))
+            .cloned()
+            .unwrap_or_default();
+        observation.mut_observation().set_game_loop(client.game_loop);
+        observation.mut_observation().mut_player_common().set_player_id(client.player_id);
+        if client.game_loop >= self.game.end_loop {
+            let joined = self.state.lock().unwrap().joined.max(client.player_id);
+            let results = (1..=joined)
+                .map(|player_id| {
+                    let mut result = PlayerResult::new();
+                    result.set_player_id(player_id);
+                    result.set_result(if player_id == 1 { GameResult::Victory } else { GameResult::Defeat });
+                    result
+                })
+                .collect();
+            observation.set_player_result(RepeatedField::from_vec(results));
+        }
+        observation
+    }
+}
+
+/// What the server knows about one connection
+#[derive(Default)]
+struct MockClient {
+    /// 0 until the client joins
+    player_id: u32,
+    race: Race,
+    game_loop: u32,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net_helpers::send_create_game_request;
+    use crate::proxy_ws::ProxyWS;
+    use sc2_proto::sc2api::{LocalMap, RequestCreateGame};
+    use tokio::sync::oneshot;
+    use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+
+    type Client = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+    async fn call(client: &mut Client, request: Request) -> Response {
+        client.send(WsMessage::binary(request.write_to_bytes().unwrap())).await.unwrap();
+        loop {
+            match client.next().await.expect("connection closed").unwrap() {
+                WsMessage::Binary(bytes) => return Response::parse_from_bytes(&bytes).unwrap(),
+                _ => continue,
+            }
+        }
+    }
+
+    fn create_game_request() -> Request {
+        let mut local_map = LocalMap::new();
+        local_map.set_map_path("Mock.SC2Map".to_string());
+        let mut create = RequestCreateGame::new();
+        create.set_local_map(local_map);
+        let mut request = Request::new();
+        request.set_create_game(create);
+        request
+    }
+
+    fn step_request(count: u32) -> Request {
+        let mut request = Request::new();
+        request.mut_step().set_count(count);
+        request
+    }
+
+    /// Start a proxy in front of `upstream` and return the address bots connect to
+    async fn start_proxy(upstream: SocketAddr, responses: Arc<Mutex<Vec<(u32, Response)>>>) -> SocketAddr {
+        let (listening_tx, listening_rx) = oneshot::channel();
+        let listening_tx = Mutex::new(Some(listening_tx));
+        let proxy = ProxyWS::new(
+            "127.0.0.1:0",
+            format!("ws://{}/sc2api", upstream),
+            move |player_id, response| responses.lock().unwrap().push((player_id, response)),
+        )
+        .with_on_listening(move |addr| {
+            if let Some(tx) = listening_tx.lock().unwrap().take() {
+                let _ = tx.send(addr);
+            }
+        });
+        tokio::spawn(proxy.run());
+        listening_rx.await.unwrap()
+    }
+
+    #[test]
+    fn synthetic_game_info_has_a_start_location() {
+        let game = MockGame::synthetic();
+        let start_raw = game.game_info.get_start_raw();
+        assert!(!start_raw.get_start_locations().is_empty());
+        assert_eq!(start_raw.get_map_size().get_x(), SYNTHETIC_MAP_SIZE.0);
+        let grid = start_raw.get_pathing_grid();
+        assert_eq!(grid.get_data().len() as i32, SYNTHETIC_MAP_SIZE.0 * SYNTHETIC_MAP_SIZE.1 / 8);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn ping_before_and_after_create_game() {
+        let upstream = MockSc2Server::new(MockGame::synthetic()).spawn("127.0.0.1:0").await.unwrap();
+        let (mut client, _) = connect_async(format!("ws://{}/sc2api", upstream)).await.unwrap();
+
+        let mut ping = Request::new();
+        ping.mut_ping();
+        let response = call(&mut client, ping.clone()).await;
+        assert!(response.has_ping());
+        assert_eq!(response.get_status(), Status::launched);
+
+        let response = call(&mut client, create_game_request()).await;
+        assert!(response.has_create_game());
+        assert!(response.get_error().is_empty());
+        assert_eq!(call(&mut client, ping).await.get_status(), Status::init_game);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn join_without_a_game_is_an_error() {
+        let upstream = MockSc2Server::new(MockGame::synthetic()).spawn("127.0.0.1:0").await.unwrap();
+        let (mut client, _) = connect_async(format!("ws://{}/sc2api", upstream)).await.unwrap();
+        let mut join = Request::new();
+        join.mut_join_game().set_race(Race::Terran);
+        let response = call(&mut client, join).await;
+        assert!(!response.has_join_game());
+        assert!(!response.get_error().is_empty());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn game_through_the_proxy() {
+        let mut game = MockGame::synthetic();
+        game.end_loop = 16;
+        let upstream = MockSc2Server::new(game).spawn("127.0.0.1:0").await.unwrap();
+
+        // Created the way aiurgaze does it, on its own connection and runtime
+        let url = format!("ws://{}/sc2api", upstream);
+        let created = tokio::task::spawn_blocking(move || {
+            std::thread::spawn(move || send_create_game_request(create_game_request(), &url, 3, 1)).join().unwrap()
+        }).await.unwrap();
+        assert_eq!(created, Ok(()));
+
+        let responses = Arc::new(Mutex::new(Vec::new()));
+        let proxy = start_proxy(upstream, responses.clone()).await;
+        let (mut bot, _) = connect_async(format!("ws://{}/sc2api", proxy)).await.unwrap();
+
+        let mut join = Request::new();
+        join.mut_join_game().set_race(Race::Zerg);
+        let response = call(&mut bot, join).await;
+        assert_eq!(response.get_join_game().get_player_id(), 1);
+        assert_eq!(response.get_status(), Status::in_game);
+
+        let mut game_info = Request::new();
+        game_info.mut_game_info();
+        let response = call(&mut bot, game_info).await;
+        let info = response.get_game_info();
+        assert!(!info.get_start_raw().get_start_locations().is_empty());
+        assert_eq!(info.get_player_info()[0].get_race_requested(), Race::Zerg);
+
+        let response = call(&mut bot, step_request(8)).await;
+        assert!(response.has_step());
+        assert_eq!(response.get_status(), Status::in_game);
+
+        let mut observation = Request::new();
+        observation.mut_observation();
+        let response = call(&mut bot, observation.clone()).await;
+        assert_eq!(response.get_observation().get_observation().get_game_loop(), 8);
+        assert!(response.get_observation().get_player_result().is_empty());
+
+        let response = call(&mut bot, step_request(8)).await;
+        assert_eq!(response.get_status(), Status::ended);
+        let response = call(&mut bot, observation).await;
+        let results = response.get_observation().get_player_result();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].get_result(), GameResult::Victory);
+
+        // The proxy reported every response, tagged with the player the server assigned
+        let responses = responses.lock().unwrap();
+        assert!(responses.iter().any(|(player_id, r)| *player_id == 1 && r.has_game_info()));
+        assert_eq!(responses.iter().filter(|(_, r)| r.has_step()).count(), 2);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn second_player_loses() {
+        let mut game = MockGame::synthetic();
+        game.end_loop = 1;
+        let upstream = MockSc2Server::new(game).spawn("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}/sc2api", upstream);
+        let (mut host, _) = connect_async(&url).await.unwrap();
+        call(&mut host, create_game_request()).await;
+
+        let mut players = Vec::new();
+        for _ in 0..2 {
+            let (mut client, _) = connect_async(&url).await.unwrap();
+            let mut join = Request::new();
+            join.mut_join_game().set_race(Race::Protoss);
+            call(&mut client, join).await;
+            players.push(client);
+        }
+        let mut observation = Request::new();
+        observation.mut_observation();
+        for (i, client) in players.iter_mut().enumerate() {
+            call(client, step_request(1)).await;
+            let response = call(client, observation.clone()).await;
+            let results = response.get_observation().get_player_result();
+            assert_eq!(results.len(), 2);
+            assert_eq!(response.get_observation().get_observation().get_player_common().get_player_id(), i as u32 + 1);
+            assert_eq!(results[0].get_result(), GameResult::Victory);
+            assert_eq!(results[1].get_result(), GameResult::Defeat);
+        }
+    }
+}
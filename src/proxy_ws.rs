@@ -1,28 +1,177 @@
 use futures_util::{future, StreamExt, SinkExt};
-use sc2_proto::sc2api::{Request, Response};
-use tokio::net::{TcpListener};
-use tokio_tungstenite::{accept_async, connect_async, tungstenite::Result};
+use sc2_proto::sc2api::{Request, RequestAction, Request_oneof_request, Response, ResponseAction, Response_oneof_response};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinSet;
+use tokio_tungstenite::{accept_async, connect_async, tungstenite::{Error as WsError, Message as WsMessage, Result}};
 
 use protobuf::Message;
-use std::sync::Arc;
+use std::io;
+use std::net::SocketAddr;
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use crate::debug_commands::{DebugQueue, take_debug_commands};
+use crate::step_control::StepGate;
+use crate::trace::{TraceDirection, TraceWriter};
+
+/// Who a latency sample measures
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LatencySource {
+    /// Time between the bot receiving an observation and sending its next step/action
+    Bot,
+    /// Time the server took to answer a request
+    Server,
+}
+
+/// One timing measured by the proxy
+#[derive(Debug, Clone)]
+pub struct LatencySample {
+    pub player_id: u32,
+    pub game_loop: u32,
+    pub source: LatencySource,
+    /// Request that ended the measurement (bot) or was answered (server)
+    pub request: &'static str,
+    pub millis: f64,
+}
+
+type LatencyCallback = Arc<dyn Fn(LatencySample) + Send + Sync>;
+
+/// One request or response as it passed through the proxy, for the protocol inspector
+#[derive(Debug, Clone)]
+pub struct ProxyMessage {
+    pub player_id: u32,
+    pub direction: TraceDirection,
+    pub kind: &'static str,
+    pub game_loop: u32,
+    /// Bot think time for step/action requests, server time for responses
+    pub latency_ms: Option<f64>,
+    pub payload: Vec<u8>,
+}
+
+type MessageCallback = Arc<dyn Fn(ProxyMessage) + Send + Sync>;
+type RequestCallback = Arc<dyn Fn(u32, Request) + Send + Sync>;
+
+/// An action request together with the response that answered it
+#[derive(Debug, Clone)]
+pub struct ActionOutcome {
+    pub player_id: u32,
+    /// Last game loop the server reported when the response arrived
+    pub game_loop: u32,
+    pub request: RequestAction,
+    pub response: ResponseAction,
+}
+
+type ActionCallback = Arc<dyn Fn(ActionOutcome) + Send + Sync>;
+
+/// A request waiting for its response
+struct InFlight {
+    kind: &'static str,
+    sent: Instant,
+    /// Injected by the proxy itself; the bot must not see the response
+    injected: bool,
+    /// Kept only for action requests, when someone listens for action outcomes
+    action: Option<RequestAction>,
+}
+
+/// Name of the request type, as spelled in the sc2api `Request` oneof
+pub fn request_kind(req: &Request) -> &'static str {
+    match &req.request {
+        Some(Request_oneof_request::create_game(_)) => "create_game",
+        Some(Request_oneof_request::join_game(_)) => "join_game",
+        Some(Request_oneof_request::restart_game(_)) => "restart_game",
+        Some(Request_oneof_request::start_replay(_)) => "start_replay",
+        Some(Request_oneof_request::leave_game(_)) => "leave_game",
+        Some(Request_oneof_request::quick_save(_)) => "quick_save",
+        Some(Request_oneof_request::quick_load(_)) => "quick_load",
+        Some(Request_oneof_request::quit(_)) => "quit",
+        Some(Request_oneof_request::game_info(_)) => "game_info",
+        Some(Request_oneof_request::observation(_)) => "observation",
+        Some(Request_oneof_request::action(_)) => "action",
+        Some(Request_oneof_request::step(_)) => "step",
+        Some(Request_oneof_request::data(_)) => "data",
+        Some(Request_oneof_request::query(_)) => "query",
+        Some(Request_oneof_request::save_replay(_)) => "save_replay",
+        Some(Request_oneof_request::replay_info(_)) => "replay_info",
+        Some(Request_oneof_request::available_maps(_)) => "available_maps",
+        Some(Request_oneof_request::save_map(_)) => "save_map",
+        Some(Request_oneof_request::ping(_)) => "ping",
+        Some(Request_oneof_request::debug(_)) => "debug",
+        // Requests added in later protocol versions
+        #[allow(unreachable_patterns)]
+        Some(_) => "other",
+        None => "unknown",
+    }
+}
+
+/// Name of the response type, as spelled in the sc2api `Response` oneof
+pub fn response_kind(res: &Response) -> &'static str {
+    match &res.response {
+        Some(Response_oneof_response::create_game(_)) => "create_game",
+        Some(Response_oneof_response::join_game(_)) => "join_game",
+        Some(Response_oneof_response::restart_game(_)) => "restart_game",
+        Some(Response_oneof_response::start_replay(_)) => "start_replay",
+        Some(Response_oneof_response::leave_game(_)) => "leave_game",
+        Some(Response_oneof_response::quick_save(_)) => "quick_save",
+        Some(Response_oneof_response::quick_load(_)) => "quick_load",
+        Some(Response_oneof_response::quit(_)) => "quit",
+        Some(Response_oneof_response::game_info(_)) => "game_info",
+        Some(Response_oneof_response::observation(_)) => "observation",
+        Some(Response_oneof_response::action(_)) => "action",
+        Some(Response_oneof_response::step(_)) => "step",
+        Some(Response_oneof_response::data(_)) => "data",
+        Some(Response_oneof_response::query(_)) => "query",
+        Some(Response_oneof_response::save_replay(_)) => "save_replay",
+        Some(Response_oneof_response::replay_info(_)) => "replay_info",
+        Some(Response_oneof_response::available_maps(_)) => "available_maps",
+        Some(Response_oneof_response::save_map(_)) => "save_map",
+        Some(Response_oneof_response::ping(_)) => "ping",
+        Some(Response_oneof_response::debug(_)) => "debug",
+        // Responses added in later protocol versions
+        #[allow(unreachable_patterns)]
+        Some(_) => "other",
+        None if !res.get_error().is_empty() => "error",
+        None => "unknown",
+    }
+}
 
 /// ProxyWS holds:
-///  * listener address for incoming client
-///  * URL of the upstream server we proxy to
-///  * callback for emitting responses
+///  * listener address for incoming clients
+///  * URLs of the upstream servers we proxy to, one per client
+///  * callback for emitting responses tagged with the player ID
+///  * optional callback fired when all clients of a game have disconnected
+///  * optional callback told the address the listener actually bound
+///  * optional callback receiving bot and server latency samples
+///  * optional callback receiving every proxied message
+///  * optional callback receiving every decoded request
+///  * optional callback receiving each action request paired with its response
+///  * optional gate holding step requests, to pause or slow down non-realtime games
+///  * optional queue of debug commands to inject between the bot's requests
+///  * optional directory for session traces
 ///
 pub struct ProxyWS<F>
 where
-    F: Fn(Response) + Send + Sync + 'static,
+    F: Fn(u32, Response) + Send + Sync + 'static,
 {
     listen_addr: String,
-    upstream_url: String,
+    upstream_urls: Vec<String>,
     on_response: Arc<F>,
+    on_game_end: Option<Box<dyn Fn() + Send + Sync>>,
+    on_listening: Option<Box<dyn Fn(SocketAddr) + Send + Sync>>,
+    on_latency: Option<LatencyCallback>,
+    on_message: Option<MessageCallback>,
+    on_request: Option<RequestCallback>,
+    on_action: Option<ActionCallback>,
+    step_gate: Option<Arc<StepGate>>,
+    debug_queue: Option<DebugQueue>,
+    /// Read when a client connects, so the directory can change between games
+    trace_dir: Arc<Mutex<Option<PathBuf>>>,
 }
 
 impl<F> ProxyWS<F>
 where
-    F: Fn(Response) + Send + Sync + 'static,
+    F: Fn(u32, Response) + Send + Sync + 'static,
 {
     pub fn new(
         listen_addr: impl Into<String>,
@@ -31,27 +180,183 @@ where
     ) -> Self {
         Self {
             listen_addr: listen_addr.into(),
-            upstream_url: upstream_url.into(),
+            upstream_urls: vec![upstream_url.into()],
             on_response: Arc::new(on_response),
+            on_game_end: None,
+            on_listening: None,
+            on_latency: None,
+            on_message: None,
+            on_request: None,
+            on_action: None,
+            step_gate: None,
+            debug_queue: None,
+            trace_dir: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Called every time the last client of a game disconnects, before listening again
+    pub fn with_on_game_end(mut self, on_game_end: impl Fn() + Send + Sync + 'static) -> Self {
+        self.on_game_end = Some(Box::new(on_game_end));
+        self
+    }
+
+    /// Called once the listener is bound, with its local address (useful when listening on port 0)
+    pub fn with_on_listening(mut self, on_listening: impl Fn(SocketAddr) + Send + Sync + 'static) -> Self {
+        self.on_listening = Some(Box::new(on_listening));
+        self
+    }
+
+    /// Called with every bot think time and server response time the sessions measure
+    pub fn with_on_latency(mut self, on_latency: impl Fn(LatencySample) + Send + Sync + 'static) -> Self {
+        self.on_latency = Some(Arc::new(on_latency));
+        self
+    }
+
+    /// Called with a copy of every request and response, for inspection
+    pub fn with_on_message(mut self, on_message: impl Fn(ProxyMessage) + Send + Sync + 'static) -> Self {
+        self.on_message = Some(Arc::new(on_message));
+        self
+    }
+
+    /// Called with every decoded request a bot sends, tagged with its player ID
+    pub fn with_on_request(mut self, on_request: impl Fn(u32, Request) + Send + Sync + 'static) -> Self {
+        self.on_request = Some(Arc::new(on_request));
+        self
+    }
+
+    /// Called with every action request of a bot and the response that answered it
+    pub fn with_on_action_result(mut self, on_action: impl Fn(ActionOutcome) + Send + Sync + 'static) -> Self {
+        self.on_action = Some(Arc::new(on_action));
+        self
+    }
+
+    /// Ask `step_gate` before forwarding each step request
+    pub fn with_step_gate(mut self, step_gate: Arc<StepGate>) -> Self {
+        self.step_gate = Some(step_gate);
+        self
+    }
+
+    /// Send the commands queued in `debug_queue` upstream before a bot's next step or observation request.
+    /// Their responses are consumed by the proxy and never reach the bot.
+    pub fn with_debug_queue(mut self, debug_queue: DebugQueue) -> Self {
+        self.debug_queue = Some(debug_queue);
+        self
+    }
+
+    /// Accept one client per upstream URL; the n-th client to connect is bridged to the n-th upstream
+    pub fn with_upstreams(mut self, upstream_urls: Vec<String>) -> Self {
+        if !upstream_urls.is_empty() {
+            self.upstream_urls = upstream_urls;
         }
+        self
+    }
+
+    /// Record every request/response of each session into a trace file under the directory in
+    /// `trace_dir`. The caller can change it while the proxy runs; `None` disables recording.
+    pub fn with_shared_trace_dir(mut self, trace_dir: Arc<Mutex<Option<PathBuf>>>) -> Self {
+        self.trace_dir = trace_dir;
+        self
     }
 
-    /// Run the proxy: wait for **one** client, then bridge traffic until closed.
+    /// Run the proxy: accept one client per upstream and bridge each pair. Once every client
+    /// of a game has disconnected, go back to listening for the next game. Runs until an error.
     pub async fn run(self) -> Result<()> {
+        let listener = TcpListener::bind(&self.listen_addr).await?;
+        let local_addr = listener.local_addr()?;
+        if let Some(on_listening) = &self.on_listening {
+            (on_listening)(local_addr);
+        }
+        let client_count = self.upstream_urls.len();
+
+        loop {
+            let mut sessions = JoinSet::new();
+            let mut next_slot = 0;
+            println!("Waiting for {} client(s) on ws://{}", client_count, local_addr);
+
+            // Keep accepting clients while earlier ones play; the game is over once all are gone
+            loop {
+                tokio::select! {
+                    accepted = listener.accept(), if next_slot < client_count => {
+                        let (client_stream, addr) = accepted?;
+                        println!("Client {}/{} connected from {}", next_slot + 1, client_count, addr);
+
+                        let session = ProxySession {
+                            slot: next_slot as u32 + 1,
+                            upstream_url: self.upstream_urls[next_slot].clone(),
+                            on_response: self.on_response.clone(),
+                            on_latency: self.on_latency.clone(),
+                            on_message: self.on_message.clone(),
+                            on_request: self.on_request.clone(),
+                            on_action: self.on_action.clone(),
+                            step_gate: self.step_gate.clone(),
+                            debug_queue: self.debug_queue.clone(),
+                            trace_dir: self.trace_dir.lock().unwrap().clone(),
+                        };
+                        sessions.spawn(session.run(client_stream));
+                        next_slot += 1;
+                    }
+                    Some(joined) = sessions.join_next() => {
+                        match joined {
+                            Ok(Err(e)) => eprintln!("Proxy session failed: {e}"),
+                            Err(e) => eprintln!("Proxy session panicked: {e}"),
+                            Ok(Ok(())) => {}
+                        }
+                        if sessions.is_empty() {
+                            break;
+                        }
+                    }
+                }
+            }
+
+            println!("All clients disconnected, game finished.");
+            if let Some(step_gate) = &self.step_gate {
+                step_gate.reset_game();
+            }
+            if let Some(on_game_end) = &self.on_game_end {
+                (on_game_end)();
+            }
+        }
+    }
+}
+
+/// One bot client bridged to its own upstream connection
+struct ProxySession<F>
+where
+    F: Fn(u32, Response) + Send + Sync + 'static,
+{
+    /// 1-based connection order, used as player ID until the server assigns one
+    slot: u32,
+    upstream_url: String,
+    on_response: Arc<F>,
+    on_latency: Option<LatencyCallback>,
+    on_message: Option<MessageCallback>,
+    on_request: Option<RequestCallback>,
+    on_action: Option<ActionCallback>,
+    step_gate: Option<Arc<StepGate>>,
+    debug_queue: Option<DebugQueue>,
+    trace_dir: Option<PathBuf>,
+}
+
+impl<F> ProxySession<F>
+where
+    F: Fn(u32, Response) + Send + Sync + 'static,
+{
+    async fn run(self, client_stream: TcpStream) -> Result<()> {
         let on_response = self.on_response.clone();
+        let slot = self.slot;
         let mut retries = 5;
         let delay_secs = 2;
         let mut last_err = None;
         //1. Connect upstream to the real server.
-        println!("Connecting upstream to {}", self.upstream_url);
+        println!("[client {slot}] Connecting upstream to {}", self.upstream_url);
         let upstream_ws = loop {
             match connect_async(&self.upstream_url).await {
                 Ok((ws, _)) => {
-                    println!("Connected to upstream.");
+                    println!("[client {slot}] Connected to upstream.");
                     break ws;
                 }
                 Err(e) => {
-                    println!("Failed to connect upstream: {}. Retries left: {}", e, retries);
+                    println!("[client {slot}] Failed to connect upstream: {}. Retries left: {}", e, retries);
                     last_err = Some(e);
                     if retries == 0 {
                         return Err(last_err.unwrap());
@@ -63,22 +368,117 @@ where
         };
         let (mut upstream_write, mut upstream_read) = upstream_ws.split();
 
-        // 3. Wait for a single client to connect.
-        let listener = TcpListener::bind(&self.listen_addr).await?;
-        println!("Waiting for client on ws://{}", self.listen_addr);
-        let (client_stream, addr) = listener.accept().await?;
-        println!("Client connected from {}", addr);
+        // 2. Finish the websocket handshake with the client.
         let client_ws = accept_async(client_stream).await?;
 
+        // 3. Open the session trace, if requested. Failing to record must not stop the game.
+        let trace = Mutex::new(self.trace_dir.as_ref().and_then(|dir| {
+            match TraceWriter::create(dir, &format!("client{slot}")) {
+                Ok(writer) => {
+                    println!("Recording session trace to {}", writer.path().display());
+                    Some(writer)
+                }
+                Err(e) => {
+                    eprintln!("Failed to create trace in {}: {e}", dir.display());
+                    None
+                }
+            }
+        }));
+        // Requests carry no game loop, so they are stamped with the last one the server reported
+        let game_loop = AtomicU32::new(0);
+        // Responses are tagged with the connection slot until JoinGame reports the real player ID
+        let player_id = AtomicU32::new(slot);
+        let record = |direction: TraceDirection, payload: &[u8]| {
+            let mut guard = trace.lock().unwrap();
+            if let Some(writer) = guard.as_mut() {
+                if let Err(e) = writer.write(direction, game_loop.load(Ordering::Relaxed), payload) {
+                    eprintln!("Failed to write trace record, recording stopped: {e}");
+                    *guard = None;
+                }
+            }
+        };
+
+        // Requests are answered in order, so the oldest unanswered one is what a response belongs to
+        let in_flight: Mutex<VecDeque<InFlight>> = Mutex::new(VecDeque::new());
+        // When the bot was last handed an observation, until it sends its next step/action
+        let observation_sent: Mutex<Option<Instant>> = Mutex::new(None);
+        let report = |source: LatencySource, request: &'static str, since: Instant| -> f64 {
+            let millis = since.elapsed().as_secs_f64() * 1000.0;
+            if let Some(on_latency) = &self.on_latency {
+                (on_latency)(LatencySample {
+                    player_id: player_id.load(Ordering::Relaxed),
+                    game_loop: game_loop.load(Ordering::Relaxed),
+                    source,
+                    request,
+                    millis,
+                });
+            }
+            millis
+        };
+        let inspect = |direction: TraceDirection, kind: &'static str, latency_ms: Option<f64>, payload: &[u8]| {
+            if let Some(on_message) = &self.on_message {
+                (on_message)(ProxyMessage {
+                    player_id: player_id.load(Ordering::Relaxed),
+                    direction,
+                    kind,
+                    game_loop: game_loop.load(Ordering::Relaxed),
+                    latency_ms,
+                    payload: payload.to_vec(),
+                });
+            }
+        };
+
         // 4. Proxy messages in both directions until either side closes.
         let (mut client_write, mut client_read) = client_ws.split();
 
         let c2s = async {
+            // Pacing of this client's steps under the speed cap
+            let mut next_step = None;
             while let Some(msg) = client_read.next().await {
                 let msg = msg?;
+                // Only binary frames carry requests; pings are answered by tungstenite itself
+                match msg {
+                    WsMessage::Binary(_) => {}
+                    WsMessage::Close(_) => {
+                        upstream_write.send(msg).await?;
+                        continue;
+                    }
+                    _ => continue,
+                }
 
+                let data = msg.clone().into_data();
                 let mut req = Request::new();
-                req.merge_from_bytes(msg.clone().into_data().iter().as_slice()).unwrap();
+                record(TraceDirection::Request, &data);
+                if let Err(e) = req.merge_from_bytes(data.iter().as_slice()) {
+                    // Forwarding it would leave `in_flight` out of step with the responses
+                    return Err(WsError::Io(io::Error::new(io::ErrorKind::InvalidData, format!("client sent a malformed request: {e}"))));
+                }
+
+                let kind = request_kind(&req);
+                let mut latency_ms = None;
+                if matches!(kind, "step" | "action") {
+                    if let Some(sent) = observation_sent.lock().unwrap().take() {
+                        latency_ms = Some(report(LatencySource::Bot, kind, sent));
+                    }
+                }
+                inspect(TraceDirection::Request, kind, latency_ms, &data);
+                if let Some(step_gate) = self.step_gate.as_ref().filter(|_| req.has_step()) {
+                    step_gate.wait_for_step(game_loop.load(Ordering::Relaxed), req.get_step().get_count(), &mut next_step).await;
+                }
+                if let Some(queue) = self.debug_queue.as_ref().filter(|_| matches!(kind, "step" | "observation")) {
+                    if let Some(debug) = take_debug_commands(queue, player_id.load(Ordering::Relaxed)) {
+                        let bytes = debug.write_to_bytes().unwrap_or_default();
+                        record(TraceDirection::Request, &bytes);
+                        inspect(TraceDirection::Request, "debug", None, &bytes);
+                        in_flight.lock().unwrap().push_back(InFlight { kind: "debug", sent: Instant::now(), injected: true, action: None });
+                        upstream_write.send(WsMessage::binary(bytes)).await?;
+                    }
+                }
+                let action = self.on_action.as_ref().filter(|_| req.has_action()).map(|_| req.get_action().clone());
+                in_flight.lock().unwrap().push_back(InFlight { kind, sent: Instant::now(), injected: false, action });
+                if let Some(on_request) = &self.on_request {
+                    (on_request)(player_id.load(Ordering::Relaxed), req);
+                }
 
                 upstream_write.send(msg).await?;
             }
@@ -88,14 +488,57 @@ where
         let s2c = async {
             while let Some(msg) = upstream_read.next().await {
                 let msg = msg?;
+                match msg {
+                    WsMessage::Binary(_) => {}
+                    WsMessage::Close(_) => {
+                        client_write.send(msg).await?;
+                        continue;
+                    }
+                    _ => continue,
+                }
 
+                let data = msg.clone().into_data();
                 let mut res = Response::new();
-                res.merge_from_bytes(msg.clone().into_data().iter().as_slice()).ok();
+                res.merge_from_bytes(data.iter().as_slice()).ok();
+                if res.has_observation() {
+                    let obs_loop = res.get_observation().get_observation().get_game_loop();
+                    game_loop.store(obs_loop, Ordering::Relaxed);
+                    if let Some(step_gate) = &self.step_gate {
+                        step_gate.observe(obs_loop);
+                    }
+                }
+                if res.has_join_game() {
+                    player_id.store(res.get_join_game().get_player_id(), Ordering::Relaxed);
+                }
+                record(TraceDirection::Response, &data);
+
+                let answered = in_flight.lock().unwrap().pop_front();
+                let latency_ms = answered.as_ref().map(|entry| report(LatencySource::Server, entry.kind, entry.sent));
+                inspect(TraceDirection::Response, response_kind(&res), latency_ms, &data);
+                if answered.as_ref().is_some_and(|entry| entry.injected) {
+                    if !res.get_error().is_empty() {
+                        eprintln!("[client {slot}] Injected debug request failed: {:?}", res.get_error());
+                    }
+                    continue;
+                }
+                let request = answered.and_then(|entry| entry.action).filter(|_| res.has_action());
+                if let (Some(on_action), Some(request)) = (&self.on_action, request) {
+                    (on_action)(ActionOutcome {
+                        player_id: player_id.load(Ordering::Relaxed),
+                        game_loop: game_loop.load(Ordering::Relaxed),
+                        request,
+                        response: res.get_action().clone(),
+                    });
+                }
+                let is_observation = res.has_observation();
                 
                 // Call the callback with the response
-                (on_response)(res);
+                (on_response)(player_id.load(Ordering::Relaxed), res);
 
                 client_write.send(msg).await?;
+                if is_observation {
+                    *observation_sent.lock().unwrap() = Some(Instant::now());
+                }
             }
             Ok::<_, tungstenite::Error>(())
         };
@@ -104,20 +547,23 @@ where
         match future::select(Box::pin(c2s), Box::pin(s2c)).await {
             future::Either::Left((res, _)) => {
                 if let Err(e) = res {
-                    eprintln!("client → server forwarding ended with error: {e}");
+                    eprintln!("[client {slot}] client → server forwarding ended with error: {e}");
                 } else {
-                    println!("client → server closed normally");
+                    println!("[client {slot}] client → server closed normally");
                 }
             }
             future::Either::Right((res, _)) => {
                 if let Err(e) = res {
-                    eprintln!("server → client forwarding ended with error: {e}");
+                    eprintln!("[client {slot}] server → client forwarding ended with error: {e}");
                 } else {
-                    println!("server → client closed normally");
+                    println!("[client {slot}] server → client closed normally");
                 }
             }
         }
-        println!("Proxy finished.");
+        if let Some(writer) = trace.lock().unwrap().as_mut() {
+            writer.flush().ok();
+        }
+        println!("[client {slot}] Session finished.");
         Ok(())
     }
 }
\ No newline at end of file
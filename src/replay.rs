@@ -0,0 +1,200 @@
+// src/replay.rs
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use protobuf::Message;
+use sc2_proto::sc2api::Response;
+use std::path::Path;
+use crate::controller::ProxyResponseEvent;
+use crate::trace::{read_trace, TraceDirection};
+
+/// Game loops per second at "faster" game speed
+const LOOPS_PER_SECOND: f32 = 22.4;
+
+/// Available playback speed multipliers
+const SPEEDS: [f32; 7] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0, 16.0];
+
+/// A recorded response together with the game loop it was observed at
+pub struct ReplayFrame {
+    pub game_loop: u32,
+    pub response: Response,
+}
+
+/// Resource driving offline playback of a recorded trace
+#[derive(Resource)]
+pub struct ReplayPlayback {
+    /// Player the trace was recorded for, from its JoinGame response
+    pub player_id: u32,
+    /// `game_info` response, sent once before any observation
+    pub game_info: Response,
+    /// Observations ordered by game loop
+    pub frames: Vec<ReplayFrame>,
+    /// Index of the next frame to emit
+    pub cursor: usize,
+    /// Current playback position in game loops
+    pub position: f32,
+    pub paused: bool,
+    pub speed: f32,
+    /// Emit the next frame once, even while paused
+    pub step_requested: bool,
+    /// Jump to this game loop on the next update
+    pub seek_to: Option<u32>,
+    game_info_sent: bool,
+}
+
+impl ReplayPlayback {
+    /// Load a trace and keep the server responses needed to redraw the game
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let records = read_trace(path)
+            .map_err(|e| format!("Failed to read trace {}: {}", path.display(), e))?;
+
+        let mut player_id = 1;
+        let mut game_info = None;
+        let mut frames = Vec::new();
+        for record in records.into_iter().filter(|r| r.direction == TraceDirection::Response) {
+            let Ok(response) = Response::parse_from_bytes(&record.payload) else {
+                continue;
+            };
+            if response.has_join_game() {
+                player_id = response.get_join_game().get_player_id();
+            } else if response.has_game_info() {
+                game_info.get_or_insert(response);
+            } else if response.has_observation() {
+                frames.push(ReplayFrame { game_loop: record.game_loop, response });
+            }
+        }
+
+        let Some(game_info) = game_info else {
+            return Err(format!("Trace {} has no game_info response, cannot draw the map", path.display()));
+        };
+        println!("[replay] Loaded {} observations from {}", frames.len(), path.display());
+
+        Ok(Self {
+            player_id,
+            game_info,
+            frames,
+            cursor: 0,
+            position: 0.0,
+            paused: false,
+            speed: 1.0,
+            step_requested: false,
+            seek_to: None,
+            game_info_sent: false,
+        })
+    }
+
+    pub fn first_loop(&self) -> u32 {
+        self.frames.first().map_or(0, |f| f.game_loop)
+    }
+
+    pub fn last_loop(&self) -> u32 {
+        self.frames.last().map_or(0, |f| f.game_loop)
+    }
+
+    /// Game loop of the last emitted frame
+    pub fn current_loop(&self) -> u32 {
+        self.cursor
+            .checked_sub(1)
+            .and_then(|i| self.frames.get(i))
+            .map_or(self.first_loop(), |f| f.game_loop)
+    }
+
+    /// Index one past the last frame at or before `game_loop`
+    fn index_after(&self, game_loop: u32) -> usize {
+        self.frames.partition_point(|f| f.game_loop <= game_loop)
+    }
+}
+
+/// System that feeds recorded responses into the regular `ProxyResponseEvent` path
+pub fn replay_playback_system(
+    time: Res<Time>,
+    mut playback: ResMut<ReplayPlayback>,
+    mut events: EventWriter<ProxyResponseEvent>,
+) {
+    // game_info spawns the tilemap through commands, so observations start on the next frame
+    if !playback.game_info_sent {
+        events.write(ProxyResponseEvent {
+            player_id: playback.player_id,
+            response: playback.game_info.clone(),
+        });
+        playback.game_info_sent = true;
+        playback.position = playback.first_loop() as f32;
+        return;
+    }
+
+    if playback.frames.is_empty() {
+        return;
+    }
+
+    let target_index = if let Some(target) = playback.seek_to.take() {
+        playback.position = target as f32;
+        playback.index_after(target).max(1)
+    } else if playback.step_requested {
+        playback.step_requested = false;
+        playback.paused = true;
+        let next = (playback.cursor + 1).min(playback.frames.len());
+        playback.position = playback.frames[next - 1].game_loop as f32;
+        next
+    } else if !playback.paused {
+        playback.position += time.delta_secs() * LOOPS_PER_SECOND * playback.speed;
+        let index = playback.index_after(playback.position as u32);
+        if index > playback.cursor { index } else { return; }
+    } else {
+        return;
+    };
+
+    if target_index == playback.cursor {
+        return;
+    }
+    playback.cursor = target_index;
+
+    // Observations carry the full unit list, so only the latest one due has to be drawn
+    let frame = &playback.frames[target_index - 1];
+    events.write(ProxyResponseEvent {
+        player_id: playback.player_id,
+        response: frame.response.clone(),
+    });
+
+    if playback.cursor >= playback.frames.len() {
+        playback.paused = true;
+    }
+}
+
+/// Timeline panel with pause, step, speed and seek controls
+pub fn replay_timeline_system(mut contexts: EguiContexts, mut playback: ResMut<ReplayPlayback>) {
+    let Ok(ctx) = contexts.ctx_mut() else { return; };
+
+    egui::TopBottomPanel::bottom("replay_timeline").show(ctx, |ui| {
+        ui.horizontal(|ui| {
+            let play_label = if playback.paused { "▶ Play" } else { "⏸ Pause" };
+            if ui.button(play_label).clicked() {
+                if playback.paused && playback.cursor >= playback.frames.len() {
+                    // Restart from the beginning when play is pressed at the end
+                    playback.seek_to = Some(playback.first_loop());
+                }
+                playback.paused = !playback.paused;
+            }
+            if ui.button("⏭ Step").clicked() {
+                playback.step_requested = true;
+            }
+
+            ui.label("Speed:");
+            egui::ComboBox::from_id_salt("replay_speed_combo")
+                .selected_text(format!("{}x", playback.speed))
+                .show_ui(ui, |ui| {
+                    for speed in SPEEDS {
+                        ui.selectable_value(&mut playback.speed, speed, format!("{}x", speed));
+                    }
+                });
+
+            ui.separator();
+            let (first, last) = (playback.first_loop(), playback.last_loop());
+            let mut seek = playback.current_loop();
+            ui.spacing_mut().slider_width = (ui.available_width() - 200.0).max(100.0);
+            let slider = ui.add(egui::Slider::new(&mut seek, first..=last).text("game loop"));
+            if slider.changed() {
+                playback.seek_to = Some(seek);
+            }
+            ui.label(format!("{} / {}", playback.cursor, playback.frames.len()));
+        });
+    });
+}
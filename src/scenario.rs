@@ -0,0 +1,201 @@
+// src/scenario.rs
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use sc2_proto::debug::DebugGameState;
+use sc2_proto::raw::Alliance;
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::path::Path;
+use crate::controller::{ProxyGameEndedEvent, ProxyResponseEvent};
+use crate::debug_commands::{create_unit, game_state, kill_units, DebugCommandQueue};
+use crate::entity_system::load_unit_type_ids;
+use crate::ui::GameConfigPanel;
+
+/// What the minerals and gas cheats add each time they are sent
+const CHEAT_RESOURCE_AMOUNT: u32 = 5000;
+
+/// Unit type, by data.json name ("Marine") or by ID
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum UnitRef {
+    Id(u32),
+    Name(String),
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ScenarioUnit {
+    pub unit: UnitRef,
+    #[serde(default = "default_count")]
+    pub count: u32,
+    /// Map coordinates, as in the game
+    pub x: f32,
+    pub y: f32,
+}
+
+impl UnitRef {
+    /// Unit type ID, looking names up in `ids` (from `load_unit_type_ids`)
+    pub fn resolve(&self, ids: &HashMap<String, u32>) -> Result<u32, String> {
+        match self {
+            UnitRef::Id(id) => Ok(*id),
+            UnitRef::Name(name) => ids.get(&name.to_lowercase()).copied()
+                .ok_or_else(|| format!("Unknown unit '{}'", name)),
+        }
+    }
+}
+
+fn default_count() -> u32 {
+    1
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ScenarioPlayer {
+    pub player_id: u32,
+    /// Added on top of the starting resources, rounded up to the cheat's 5000 steps
+    #[serde(default)]
+    pub minerals: u32,
+    #[serde(default)]
+    pub vespene: u32,
+    /// Times the upgrade cheat is sent; each raises every upgrade by one level
+    #[serde(default)]
+    pub upgrade_levels: u32,
+    /// Remove tech requirements
+    #[serde(default)]
+    pub tech_tree: bool,
+    #[serde(default)]
+    pub units: Vec<ScenarioUnit>,
+}
+
+/// Starting setup applied with debug commands once the game is running, loaded from TOML or JSON
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Scenario {
+    /// Map file from ./maps; overrides the map picked in the panel
+    #[serde(default)]
+    pub map: Option<String>,
+    /// Kill the starting workers and town halls first.
+    /// Units hidden by fog can't be found, so disable fog to clear a computer opponent too.
+    #[serde(default = "default_clear_units")]
+    pub clear_units: bool,
+    #[serde(default)]
+    pub players: Vec<ScenarioPlayer>,
+}
+
+fn default_clear_units() -> bool {
+    true
+}
+
+impl Scenario {
+    /// Load a scenario, as JSON if the file ends in .json and TOML otherwise, and resolve its unit names
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read scenario {}: {}", path.display(), e))?;
+        let mut scenario: Scenario = if path.extension().is_some_and(|ext| ext == "json") {
+            serde_json::from_str(&content).map_err(|e| format!("Failed to parse scenario {}: {}", path.display(), e))?
+        } else {
+            toml::from_str(&content).map_err(|e| format!("Failed to parse scenario {}: {}", path.display(), e))?
+        };
+
+        let needs_names = scenario.players.iter()
+            .flat_map(|p| &p.units)
+            .any(|u| matches!(u.unit, UnitRef::Name(_)));
+        if needs_names {
+            let ids = load_unit_type_ids()?;
+            for unit in scenario.players.iter_mut().flat_map(|p| p.units.iter_mut()) {
+                let id = unit.unit.resolve(&ids).map_err(|e| format!("{} in scenario {}", e, path.display()))?;
+                unit.unit = UnitRef::Id(id);
+            }
+        }
+        Ok(scenario)
+    }
+}
+
+/// Progress of the current game's scenario
+#[derive(Resource, Default)]
+pub struct ScenarioState {
+    /// Units are cleared and created once per game, from the first observation
+    units_placed: bool,
+    /// Players whose resource and upgrade cheats were queued
+    players_applied: BTreeSet<u32>,
+    scenario: Option<Scenario>,
+}
+
+/// System to set up the panel's scenario through the debug queue once each player observes the new game.
+/// Units go through the first connection; cheats only affect the player sending them, so they wait for that player's.
+pub fn scenario_system(
+    mut responses: EventReader<ProxyResponseEvent>,
+    mut game_ended: EventReader<ProxyGameEndedEvent>,
+    mut state: ResMut<ScenarioState>,
+    panel: Res<GameConfigPanel>,
+    queue: Res<DebugCommandQueue>,
+) {
+    if game_ended.read().last().is_some() {
+        if let Some(scenario) = state.scenario.take() {
+            for player in scenario.players.iter().filter(|p| !state.players_applied.contains(&p.player_id)) {
+                if player.minerals > 0 || player.vespene > 0 || player.upgrade_levels > 0 || player.tech_tree {
+                    eprintln!("[scenario] Player {} never connected through the proxy, its resources and upgrades were not applied", player.player_id);
+                }
+            }
+        }
+        *state = ScenarioState::default();
+    }
+
+    let Some(path) = panel.scenario.as_deref().filter(|p| !p.is_empty()) else {
+        responses.clear();
+        return;
+    };
+
+    for event in responses.read() {
+        if !event.response.has_observation() || state.players_applied.contains(&event.player_id) {
+            continue;
+        }
+        if state.scenario.is_none() {
+            match Scenario::load(Path::new(path)) {
+                Ok(scenario) => state.scenario = Some(scenario),
+                Err(e) => {
+                    eprintln!("[scenario] {}", e);
+                    // Don't retry on every observation of this game
+                    state.units_placed = true;
+                    state.players_applied.insert(event.player_id);
+                    continue;
+                }
+            }
+        }
+        let Some(scenario) = state.scenario.clone() else { continue; };
+        let observation = event.response.get_observation().get_observation();
+
+        if !state.units_placed {
+            state.units_placed = true;
+            if scenario.clear_units {
+                let tags: Vec<u64> = observation.get_raw_data().get_units().iter()
+                    .filter(|u| u.get_alliance() != Alliance::Neutral)
+                    .map(|u| u.get_tag())
+                    .collect();
+                if !tags.is_empty() {
+                    queue.push(Some(event.player_id), kill_units(tags));
+                }
+            }
+            for player in &scenario.players {
+                for unit in &player.units {
+                    if let UnitRef::Id(unit_type) = unit.unit {
+                        queue.push(Some(event.player_id), create_unit(unit_type, player.player_id, unit.x, unit.y, unit.count));
+                    }
+                }
+            }
+            println!("[scenario] Placing units of {} at loop {}", path, observation.get_game_loop());
+        }
+
+        state.players_applied.insert(event.player_id);
+        if let Some(player) = scenario.players.iter().find(|p| p.player_id == event.player_id) {
+            let cheats = [
+                (DebugGameState::minerals, player.minerals.div_ceil(CHEAT_RESOURCE_AMOUNT)),
+                (DebugGameState::gas, player.vespene.div_ceil(CHEAT_RESOURCE_AMOUNT)),
+                (DebugGameState::upgrade, player.upgrade_levels),
+                (DebugGameState::tech_tree, player.tech_tree as u32),
+            ];
+            for (cheat, times) in cheats {
+                for _ in 0..times {
+                    queue.push(Some(event.player_id), game_state(cheat));
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,503 @@
+// src/series.rs
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use sc2_proto::common::Race;
+use sc2_proto::sc2api::Result as GameResult;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::controller::{ProxyGameEndedEvent, ProxyResponseEvent};
+use crate::ui::{build_create_game_request, CreateGameFailedEvent, GameConfigPanel, GameType, PendingCreateGameRequest};
+use crate::ui::game_config_panel::{parse_ai_build, parse_difficulty, parse_race};
+
+/// Our bot is the first entry of `player_setup`, so the server gives it player ID 1
+const PARTICIPANT_PLAYER_ID: u32 = 1;
+
+/// Opponent of a series, a built-in computer AI or another bot
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type")]
+pub enum SeriesOpponent {
+    Computer {
+        difficulty: String,
+        #[serde(default)]
+        race: Option<String>,
+        #[serde(default)]
+        build: Option<String>,
+    },
+    Bot {
+        name: String,
+        command: String,
+        #[serde(default)]
+        race: Option<String>,
+    },
+}
+
+impl SeriesOpponent {
+    /// Short name used in the results table
+    pub fn label(&self) -> String {
+        match self {
+            SeriesOpponent::Computer { difficulty, race, build } => {
+                let mut label = format!("AI {}", difficulty);
+                if let Some(race) = race {
+                    label.push_str(&format!(" {}", race));
+                }
+                if let Some(build) = build {
+                    label.push_str(&format!(" {}", build));
+                }
+                label
+            }
+            SeriesOpponent::Bot { name, .. } => name.clone(),
+        }
+    }
+
+    /// Check the names that are only parsed when the game is created, where a typo would fall back silently
+    fn validate(&self) -> Result<(), String> {
+        let race = match self {
+            SeriesOpponent::Computer { difficulty, race, build } => {
+                if parse_difficulty(difficulty).is_none() {
+                    return Err(format!(
+                        "Invalid difficulty '{difficulty}'. Allowed difficulties: VeryEasy, Easy, Medium, MediumHard, \
+                         Hard, Harder, VeryHard, CheatVision, CheatMoney, CheatInsane"
+                    ));
+                }
+                if let Some(build) = build.as_deref().filter(|b| parse_ai_build(b).is_none()) {
+                    return Err(format!("Invalid AI build '{build}'. Allowed builds: random, rush, timing, power, macro, air"));
+                }
+                race
+            }
+            SeriesOpponent::Bot { race, .. } => race,
+        };
+        match race.as_deref().filter(|r| parse_race(r).is_none()) {
+            Some(race) => Err(format!("Invalid race '{race}'")),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Series definition, loaded from a TOML file
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SeriesConfig {
+    /// Games to play for every map/opponent combination
+    pub games: u32,
+    pub maps: Vec<String>,
+    /// Races our bot cycles through from one game to the next
+    #[serde(default = "default_races")]
+    pub races: Vec<String>,
+    pub opponents: Vec<SeriesOpponent>,
+    /// Overrides the player bot command from config.toml
+    #[serde(default)]
+    pub bot_command: Option<String>,
+    /// Where the results table is exported
+    #[serde(default = "default_output_dir")]
+    pub output_dir: String,
+}
+
+fn default_races() -> Vec<String> {
+    vec!["Random".to_string()]
+}
+
+fn default_output_dir() -> String {
+    "series".to_string()
+}
+
+impl SeriesConfig {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read series {}: {}", path.display(), e))?;
+        let config: SeriesConfig = toml::from_str(&content)
+            .map_err(|e| format!("Failed to parse series {}: {}", path.display(), e))?;
+        if config.maps.is_empty() || config.opponents.is_empty() || config.games == 0 {
+            return Err("A series needs at least one map, one opponent and one game".to_string());
+        }
+        if let Some(race) = config.races.iter().find(|r| parse_race(r).is_none()) {
+            return Err(format!("Invalid race '{}' in series", race));
+        }
+        for opponent in &config.opponents {
+            opponent.validate().map_err(|e| format!("{} for opponent '{}' in series", e, opponent.label()))?;
+        }
+        Ok(config)
+    }
+}
+
+/// One scheduled game of the series
+#[derive(Debug, Clone)]
+pub struct SeriesGame {
+    pub map: String,
+    pub player_race: String,
+    pub opponent: SeriesOpponent,
+}
+
+/// How a series game ended for our bot
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum SeriesOutcome {
+    Victory,
+    Defeat,
+    Tie,
+    Undecided,
+    /// The game ended without a result
+    Unknown,
+    /// The game could not be created
+    Error,
+}
+
+impl SeriesOutcome {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SeriesOutcome::Victory => "Victory",
+            SeriesOutcome::Defeat => "Defeat",
+            SeriesOutcome::Tie => "Tie",
+            SeriesOutcome::Undecided => "Undecided",
+            SeriesOutcome::Unknown => "Unknown",
+            SeriesOutcome::Error => "Error",
+        }
+    }
+}
+
+impl From<GameResult> for SeriesOutcome {
+    fn from(result: GameResult) -> Self {
+        match result {
+            GameResult::Victory => SeriesOutcome::Victory,
+            GameResult::Defeat => SeriesOutcome::Defeat,
+            GameResult::Tie => SeriesOutcome::Tie,
+            GameResult::Undecided => SeriesOutcome::Undecided,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SeriesGameResult {
+    pub game: usize,
+    pub map: String,
+    pub player_race: String,
+    pub opponent: String,
+    pub result: SeriesOutcome,
+    pub game_loops: u32,
+}
+
+/// Aggregated results for one map/opponent combination
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SeriesStanding {
+    pub wins: u32,
+    pub losses: u32,
+    pub other: u32,
+}
+
+impl SeriesStanding {
+    pub fn win_rate(&self) -> f32 {
+        let total = self.wins + self.losses + self.other;
+        if total == 0 { 0.0 } else { self.wins as f32 / total as f32 }
+    }
+}
+
+/// Resource running the games of a series back to back
+#[derive(Resource)]
+pub struct SeriesRunner {
+    pub config: SeriesConfig,
+    pub games: Vec<SeriesGame>,
+    /// Index of the game in progress, if any
+    pub current: Option<usize>,
+    pub next: usize,
+    pub results: Vec<SeriesGameResult>,
+    current_result: Option<SeriesOutcome>,
+    current_game_loop: u32,
+}
+
+impl SeriesRunner {
+    pub fn new(config: SeriesConfig) -> Self {
+        let mut games = Vec::new();
+        for map in &config.maps {
+            for opponent in &config.opponents {
+                for _ in 0..config.games {
+                    let player_race = config.races[games.len() % config.races.len()].clone();
+                    games.push(SeriesGame { map: map.clone(), player_race, opponent: opponent.clone() });
+                }
+            }
+        }
+        println!("[series] Scheduled {} games", games.len());
+        Self {
+            config,
+            games,
+            current: None,
+            next: 0,
+            results: Vec::new(),
+            current_result: None,
+            current_game_loop: 0,
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.current.is_none() && self.next >= self.games.len()
+    }
+
+    /// Win/loss counts keyed by (map, opponent)
+    pub fn standings(&self) -> BTreeMap<(String, String), SeriesStanding> {
+        let mut standings: BTreeMap<(String, String), SeriesStanding> = BTreeMap::new();
+        for result in &self.results {
+            let standing = standings.entry((result.map.clone(), result.opponent.clone())).or_default();
+            match result.result {
+                SeriesOutcome::Victory => standing.wins += 1,
+                SeriesOutcome::Defeat => standing.losses += 1,
+                _ => standing.other += 1,
+            }
+        }
+        standings
+    }
+
+    /// Record the result of the game in progress and export the results so far
+    fn finish_current(&mut self) {
+        let Some(index) = self.current.take() else { return; };
+        let game = self.games[index].clone();
+        let result = SeriesGameResult {
+            game: index + 1,
+            map: game.map,
+            player_race: game.player_race,
+            opponent: game.opponent.label(),
+            result: self.current_result.take().unwrap_or(SeriesOutcome::Unknown),
+            game_loops: self.current_game_loop,
+        };
+        println!("[series] Game {}/{} finished: {}", result.game, self.games.len(), result.result.as_str());
+        self.results.push(result);
+        self.current_game_loop = 0;
+
+        // Export after every game so an interrupted series keeps what it played
+        match self.export() {
+            Ok(dir) if self.is_finished() => println!("[series] Series complete, results in {}", dir.display()),
+            Ok(_) => {}
+            Err(e) => eprintln!("[series] {}", e),
+        }
+    }
+
+    /// Write the results as CSV and JSON into the series output directory
+    pub fn export(&self) -> Result<PathBuf, String> {
+        let dir = PathBuf::from(&self.config.output_dir);
+        fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+
+        let mut csv = String::from("game,map,player_race,opponent,result,game_loops\n");
+        for r in &self.results {
+            csv.push_str(&format!(
+                "{},{},{},{},{},{}\n",
+                r.game, csv_field(&r.map), csv_field(&r.player_race), csv_field(&r.opponent), r.result.as_str(), r.game_loops
+            ));
+        }
+        fs::write(dir.join("results.csv"), csv).map_err(|e| format!("Failed to write results.csv: {}", e))?;
+
+        let standings: Vec<_> = self.standings().into_iter().map(|((map, opponent), s)| {
+            serde_json::json!({
+                "map": map,
+                "opponent": opponent,
+                "wins": s.wins,
+                "losses": s.losses,
+                "other": s.other,
+                "win_rate": s.win_rate(),
+            })
+        }).collect();
+        let json = serde_json::json!({ "games": self.results, "standings": standings });
+        let json = serde_json::to_string_pretty(&json).map_err(|e| e.to_string())?;
+        fs::write(dir.join("results.json"), json).map_err(|e| format!("Failed to write results.json: {}", e))?;
+        Ok(dir)
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Point the game config panel at one scheduled game.
+/// Every field is set, so nothing of the previous game (e.g. the opponent's race) carries over;
+/// only the lists, the player bot from the config and the game options from the config are kept.
+fn configure_panel(panel: &mut GameConfigPanel, game: &SeriesGame, bot_command: Option<&String>) {
+    let (bot_command, player_bot) = match bot_command {
+        Some(command) => (command.clone(), None),
+        None => (std::mem::take(&mut panel.bot_command), panel.player_bot.take()),
+    };
+    let (game_type, ai_difficulty, ai_race, ai_build, bot_name, bot_opponent_command) = match &game.opponent {
+        SeriesOpponent::Computer { difficulty, race, build } => (
+            GameType::VsAI,
+            Some(difficulty.clone()),
+            race,
+            build.as_deref().and_then(parse_ai_build),
+            None,
+            String::new(),
+        ),
+        SeriesOpponent::Bot { name, command, race } => (
+            GameType::VsBot,
+            None,
+            race,
+            None,
+            Some(name.clone()),
+            command.clone(),
+        ),
+    };
+    *panel = GameConfigPanel {
+        game_type,
+        map_name: Some(game.map.clone()),
+        available_maps: std::mem::take(&mut panel.available_maps),
+        player_name: std::mem::take(&mut panel.player_name),
+        player_race: parse_race(&game.player_race).unwrap_or(Race::Random),
+        ai_difficulty,
+        ai_race: Some(ai_race.as_deref().and_then(parse_race).unwrap_or(Race::Random)),
+        ai_build,
+        bot_name,
+        disable_fog: panel.disable_fog,
+        random_seed: panel.random_seed,
+        realtime: panel.realtime,
+        bot_command,
+        bot_opponent_command,
+        player_bot,
+        opponent_bot: None,
+        available_bots: std::mem::take(&mut panel.available_bots),
+        auto_restart: false,
+        scenario: None,
+    };
+}
+
+/// System to queue the next game of the series and collect the results of finished ones
+pub fn series_system(
+    mut runner: ResMut<SeriesRunner>,
+    mut responses: EventReader<ProxyResponseEvent>,
+    mut game_ended: EventReader<ProxyGameEndedEvent>,
+    mut create_failed: EventReader<CreateGameFailedEvent>,
+    mut panel: ResMut<GameConfigPanel>,
+    mut pending_request: ResMut<PendingCreateGameRequest>,
+) {
+    for event in responses.read() {
+        if event.player_id != PARTICIPANT_PLAYER_ID || !event.response.has_observation() {
+            continue;
+        }
+        let obs = event.response.get_observation();
+        runner.current_game_loop = obs.get_observation().get_game_loop();
+        if let Some(result) = obs.get_player_result().iter().find(|r| r.get_player_id() == PARTICIPANT_PLAYER_ID) {
+            runner.current_result = Some(result.get_result().into());
+        }
+    }
+
+    // A game that never started has no game end to wait for
+    if let Some(CreateGameFailedEvent(e)) = create_failed.read().last() {
+        if runner.current.is_some() {
+            eprintln!("[series] Game could not be created: {}", e);
+            runner.current_result = Some(SeriesOutcome::Error);
+            runner.finish_current();
+        }
+    }
+
+    if game_ended.read().last().is_some() {
+        runner.finish_current();
+    }
+
+    if runner.current.is_some() || runner.next >= runner.games.len() || pending_request.0.is_some() {
+        return;
+    }
+
+    let index = runner.next;
+    runner.next += 1;
+    let game = runner.games[index].clone();
+    configure_panel(&mut panel, &game, runner.config.bot_command.as_ref());
+    match build_create_game_request(&panel) {
+        Ok(req) => {
+            println!("[series] Starting game {}/{}: {} vs {}", index + 1, runner.games.len(), game.map, game.opponent.label());
+            pending_request.0 = Some(req);
+            runner.current = Some(index);
+            runner.current_result = None;
+        }
+        Err(e) => eprintln!("[series] Skipping game {}: {}", index + 1, e),
+    }
+}
+
+/// Window with the series progress and win rates per map and opponent
+pub fn series_panel_system(mut contexts: EguiContexts, runner: Res<SeriesRunner>) {
+    let Ok(ctx) = contexts.ctx_mut() else { return; };
+
+    egui::Window::new("Series").default_open(true).show(ctx, |ui| {
+        ui.label(format!("Played {} / {}", runner.results.len(), runner.games.len()));
+        ui.separator();
+        egui::Grid::new("series_standings").striped(true).show(ui, |ui| {
+            ui.strong("Map");
+            ui.strong("Opponent");
+            ui.strong("W");
+            ui.strong("L");
+            ui.strong("Other");
+            ui.strong("Win rate");
+            ui.end_row();
+            for ((map, opponent), standing) in runner.standings() {
+                ui.label(map);
+                ui.label(opponent);
+                ui.label(standing.wins.to_string());
+                ui.label(standing.losses.to_string());
+                ui.label(standing.other.to_string());
+                ui.label(format!("{:.0}%", standing.win_rate() * 100.0));
+                ui.end_row();
+            }
+        });
+        if ui.button("Export CSV/JSON").clicked() {
+            match runner.export() {
+                Ok(dir) => println!("[series] Exported results to {}", dir.display()),
+                Err(e) => eprintln!("[series] {}", e),
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn runner(results: &[(&str, &str, SeriesOutcome)]) -> SeriesRunner {
+        let config: SeriesConfig = toml::from_str(r#"
+            games = 1
+            maps = ["A"]
+            [[opponents]]
+            type = "Computer"
+            difficulty = "Easy"
+        "#).unwrap();
+        let mut runner = SeriesRunner::new(config);
+        runner.results = results.iter().enumerate().map(|(i, (map, opponent, result))| SeriesGameResult {
+            game: i + 1,
+            map: map.to_string(),
+            player_race: "Random".to_string(),
+            opponent: opponent.to_string(),
+            result: *result,
+            game_loops: 0,
+        }).collect();
+        runner
+    }
+
+    #[test]
+    fn standings_count_per_map_and_opponent() {
+        let runner = runner(&[
+            ("A", "AI Easy", SeriesOutcome::Victory),
+            ("A", "AI Easy", SeriesOutcome::Defeat),
+            ("A", "AI Easy", SeriesOutcome::Victory),
+            ("A", "AI Easy", SeriesOutcome::Error),
+            ("B", "AI Easy", SeriesOutcome::Tie),
+        ]);
+        let standings = runner.standings();
+
+        let a = &standings[&("A".to_string(), "AI Easy".to_string())];
+        assert_eq!((a.wins, a.losses, a.other), (2, 1, 1));
+        assert_eq!(a.win_rate(), 0.5);
+        let b = &standings[&("B".to_string(), "AI Easy".to_string())];
+        assert_eq!((b.wins, b.losses, b.other), (0, 0, 1));
+        assert_eq!(b.win_rate(), 0.0);
+    }
+
+    #[test]
+    fn csv_fields_are_quoted_only_when_needed() {
+        assert_eq!(csv_field("AI Easy"), "AI Easy");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+    }
+
+    #[test]
+    fn unknown_difficulty_is_rejected() {
+        let opponent = SeriesOpponent::Computer { difficulty: "Meduim".to_string(), race: None, build: None };
+        assert!(opponent.validate().is_err());
+        let opponent = SeriesOpponent::Computer { difficulty: "hard".to_string(), race: None, build: Some("rush".to_string()) };
+        assert!(opponent.validate().is_ok());
+    }
+}
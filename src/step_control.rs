@@ -0,0 +1,190 @@
+// src/step_control.rs
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+use tokio::time::Instant;
+
+/// Pause, single-step and speed cap of non-realtime games.
+/// The proxy asks the gate before forwarding each `step` request; realtime games never step, so they are unaffected.
+#[derive(Default)]
+pub struct StepGate {
+    state: Mutex<StepGateState>,
+    changed: Notify,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct StepGateState {
+    pub paused: bool,
+    /// While paused, steps are still forwarded for clients below this game loop
+    pub run_until: Option<u32>,
+    /// Game loops per second each client may advance; `None` for no cap
+    pub loops_per_sec: Option<f32>,
+    /// Highest game loop any client has observed
+    pub latest_loop: u32,
+    /// Step requests currently held back
+    pub held: usize,
+}
+
+impl StepGate {
+    pub fn state(&self) -> StepGateState {
+        self.state.lock().unwrap().clone()
+    }
+
+    fn update(&self, change: impl FnOnce(&mut StepGateState)) {
+        change(&mut self.state.lock().unwrap());
+        self.changed.notify_waiters();
+    }
+
+    pub fn pause(&self) {
+        self.update(|s| {
+            s.paused = true;
+            s.run_until = None;
+        });
+    }
+
+    pub fn resume(&self) {
+        self.update(|s| {
+            s.paused = false;
+            s.run_until = None;
+        });
+    }
+
+    /// While paused, let the game advance `loops` game loops past the latest one
+    pub fn advance(&self, loops: u32) {
+        self.update(|s| s.run_until = Some(s.latest_loop + loops.max(1)));
+    }
+
+    pub fn set_rate(&self, loops_per_sec: Option<f32>) {
+        self.update(|s| s.loops_per_sec = loops_per_sec.filter(|r| *r > 0.0));
+    }
+
+    /// Record a game loop a client observed
+    pub fn observe(&self, game_loop: u32) {
+        let mut state = self.state.lock().unwrap();
+        state.latest_loop = state.latest_loop.max(game_loop);
+    }
+
+    /// Forget the finished game's loops; pause and speed cap carry over to the next game
+    pub fn reset_game(&self) {
+        self.update(|s| {
+            s.latest_loop = 0;
+            s.run_until = None;
+        });
+    }
+
+    fn may_step(&self, game_loop: u32) -> bool {
+        let state = self.state.lock().unwrap();
+        !state.paused || state.run_until.is_some_and(|until| game_loop < until)
+    }
+
+    /// Wait until a client at `game_loop` may forward a step of `count` loops.
+    /// `next_allowed` is the client's own pacing for the speed cap.
+    pub async fn wait_for_step(&self, game_loop: u32, count: u32, next_allowed: &mut Option<Instant>) {
+        let mut held = None;
+        loop {
+            // Created before checking, so a change between the check and the await still wakes us
+            let notified = self.changed.notified();
+            if self.may_step(game_loop) {
+                break;
+            }
+            held.get_or_insert_with(|| HeldStep::new(&self.state));
+            notified.await;
+        }
+        drop(held);
+
+        let Some(rate) = self.state.lock().unwrap().loops_per_sec else {
+            *next_allowed = None;
+            return;
+        };
+        let now = Instant::now();
+        let at = next_allowed.filter(|at| *at > now).unwrap_or(now);
+        tokio::time::sleep_until(at).await;
+        *next_allowed = Some(at + Duration::from_secs_f32(count.max(1) as f32 / rate));
+    }
+}
+
+/// Counts a held step request for as long as it lives, including when the session is dropped while waiting
+struct HeldStep<'a>(&'a Mutex<StepGateState>);
+
+impl<'a> HeldStep<'a> {
+    fn new(state: &'a Mutex<StepGateState>) -> Self {
+        state.lock().unwrap().held += 1;
+        Self(state)
+    }
+}
+
+impl Drop for HeldStep<'_> {
+    fn drop(&mut self) {
+        self.0.lock().unwrap().held -= 1;
+    }
+}
+
+/// Resource sharing the step gate with the proxy
+#[derive(Resource, Clone, Default)]
+pub struct StepControl(pub Arc<StepGate>);
+
+/// Settings of the step control window between frames
+#[derive(Resource)]
+pub struct StepControlPanel {
+    pub advance_loops: u32,
+    pub rate_limited: bool,
+    pub loops_per_sec: f32,
+}
+
+impl Default for StepControlPanel {
+    fn default() -> Self {
+        // 22.4 loops per second is the "faster" game speed
+        Self { advance_loops: 16, rate_limited: false, loops_per_sec: 22.4 }
+    }
+}
+
+/// Window to pause, single-step or slow down a non-realtime game
+pub fn step_control_panel_system(
+    mut contexts: EguiContexts,
+    control: Res<StepControl>,
+    mut panel: ResMut<StepControlPanel>,
+) {
+    let Ok(ctx) = contexts.ctx_mut() else { return; };
+    let gate = &control.0;
+    let state = gate.state();
+
+    egui::Window::new("Step control").default_open(false).show(ctx, |ui| {
+        ui.label("Holds the bots' step requests; has no effect on realtime games.");
+        ui.horizontal(|ui| {
+            if state.paused {
+                if ui.button("▶ Resume").clicked() {
+                    gate.resume();
+                }
+                if ui.button("Step").clicked() {
+                    gate.advance(1);
+                }
+            } else if ui.button("⏸ Pause").clicked() {
+                gate.pause();
+            }
+        });
+        ui.add_enabled_ui(state.paused, |ui| {
+            ui.horizontal(|ui| {
+                ui.add(egui::DragValue::new(&mut panel.advance_loops).range(1..=100_000).suffix(" loops"));
+                if ui.button("Advance").clicked() {
+                    gate.advance(panel.advance_loops);
+                }
+            });
+        });
+        ui.horizontal(|ui| {
+            let mut changed = ui.checkbox(&mut panel.rate_limited, "Limit to").changed();
+            changed |= ui.add(egui::DragValue::new(&mut panel.loops_per_sec).range(0.5..=1000.0).speed(0.5).suffix(" loops/s")).changed();
+            if changed {
+                gate.set_rate(panel.rate_limited.then_some(panel.loops_per_sec));
+            }
+        });
+        ui.separator();
+        let status = match (state.paused, state.run_until) {
+            (false, _) => "Running".to_string(),
+            (true, Some(until)) if state.latest_loop < until => format!("Advancing to loop {}", until),
+            (true, _) => "Paused".to_string(),
+        };
+        ui.label(format!("{} at loop {}, {} step(s) held", status, state.latest_loop, state.held));
+    });
+}
@@ -0,0 +1,215 @@
+// src/trace.rs
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Magic bytes written at the start of every trace file
+pub const TRACE_MAGIC: &[u8; 8] = b"AIURTRC1";
+
+/// Extension used for trace files
+pub const TRACE_EXTENSION: &str = "sc2trace";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceDirection {
+    /// Client (bot) → server
+    Request,
+    /// Server → client (bot)
+    Response,
+}
+
+impl TraceDirection {
+    pub fn to_byte(self) -> u8 {
+        match self {
+            TraceDirection::Request => 0,
+            TraceDirection::Response => 1,
+        }
+    }
+
+    pub fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(TraceDirection::Request),
+            1 => Some(TraceDirection::Response),
+            _ => None,
+        }
+    }
+}
+
+/// Size of the fixed record header: direction (u8) + game loop (u32) + wall clock ms (u64)
+pub const RECORD_HEADER_LEN: usize = 1 + 4 + 8;
+
+/// Writes a proxied session as a sequence of length-prefixed records.
+///
+/// File layout:
+///   * `TRACE_MAGIC`
+///   * repeated records: `u32 len | u8 direction | u32 game_loop | u64 wall_clock_ms | protobuf bytes`
+///
+/// All integers are little-endian, `len` covers everything after itself.
+pub struct TraceWriter {
+    file: BufWriter<File>,
+    path: PathBuf,
+}
+
+impl TraceWriter {
+    /// Create a new timestamped trace file inside `dir`, creating the directory if needed.
+    /// `label` tells apart sessions started at the same moment (e.g. one per bot client).
+    pub fn create(dir: &Path, label: &str) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let path = dir.join(format!("session-{}-{}.{}", unix_millis(), label, TRACE_EXTENSION));
+        Self::create_at(path)
+    }
+
+    /// Create a trace file at an exact path
+    pub fn create_at(path: PathBuf) -> io::Result<Self> {
+        let mut file = BufWriter::new(File::create(&path)?);
+        file.write_all(TRACE_MAGIC)?;
+        Ok(Self { file, path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append one message to the trace
+    pub fn write(&mut self, direction: TraceDirection, game_loop: u32, payload: &[u8]) -> io::Result<()> {
+        let len = (RECORD_HEADER_LEN + payload.len()) as u32;
+        self.file.write_all(&len.to_le_bytes())?;
+        self.file.write_all(&[direction.to_byte()])?;
+        self.file.write_all(&game_loop.to_le_bytes())?;
+        self.file.write_all(&unix_millis().to_le_bytes())?;
+        self.file.write_all(payload)?;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Drop for TraceWriter {
+    fn drop(&mut self) {
+        let _ = self.file.flush();
+    }
+}
+
+/// Milliseconds since the unix epoch
+pub fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// One message read back from a trace file
+#[derive(Debug, Clone)]
+pub struct TraceRecord {
+    pub direction: TraceDirection,
+    pub game_loop: u32,
+    pub wall_clock_ms: u64,
+    pub payload: Vec<u8>,
+}
+
+/// Read every record from a trace file written by `TraceWriter`
+pub fn read_trace(path: &Path) -> io::Result<Vec<TraceRecord>> {
+    let bytes = fs::read(path)?;
+    if bytes.len() < TRACE_MAGIC.len() || &bytes[..TRACE_MAGIC.len()] != TRACE_MAGIC {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "not an aiurgaze trace file"));
+    }
+
+    let mut records = Vec::new();
+    let mut pos = TRACE_MAGIC.len();
+    while pos + 4 <= bytes.len() {
+        let len = u32::from_le_bytes(bytes[pos..pos + 4].try_into().unwrap()) as usize;
+        pos += 4;
+        if len < RECORD_HEADER_LEN || pos + len > bytes.len() {
+            // A truncated tail means the session was killed mid-write; keep what we have
+            eprintln!("[trace] Truncated record at offset {} in {}", pos - 4, path.display());
+            break;
+        }
+        let record = &bytes[pos..pos + len];
+        let Some(direction) = TraceDirection::from_byte(record[0]) else {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("bad direction byte {}", record[0])));
+        };
+        records.push(TraceRecord {
+            direction,
+            game_loop: u32::from_le_bytes(record[1..5].try_into().unwrap()),
+            wall_clock_ms: u64::from_le_bytes(record[5..13].try_into().unwrap()),
+            payload: record[RECORD_HEADER_LEN..].to_vec(),
+        });
+        pos += len;
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Empty scratch directory for one test
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("aiurgaze-trace-{}-{}", name, std::process::id()));
+        fs::remove_dir_all(&dir).ok();
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write_sample(path: &Path) {
+        let mut writer = TraceWriter::create_at(path.to_path_buf()).unwrap();
+        writer.write(TraceDirection::Request, 0, b"ping").unwrap();
+        writer.write(TraceDirection::Response, 16, &[0, 1, 2, 255]).unwrap();
+        writer.write(TraceDirection::Request, 32, b"").unwrap();
+    }
+
+    #[test]
+    fn records_round_trip() {
+        let dir = temp_dir("round-trip");
+        let before = unix_millis();
+        let mut writer = TraceWriter::create(&dir, "client1").unwrap();
+        let path = writer.path().to_path_buf();
+        writer.write(TraceDirection::Request, 0, b"ping").unwrap();
+        writer.write(TraceDirection::Response, 16, &[0, 1, 2, 255]).unwrap();
+        writer.write(TraceDirection::Request, 32, b"").unwrap();
+        drop(writer);
+
+        assert_eq!(path.extension().and_then(|e| e.to_str()), Some(TRACE_EXTENSION));
+        let records = read_trace(&path).unwrap();
+        let read: Vec<_> = records.iter().map(|r| (r.direction, r.game_loop, r.payload.as_slice())).collect();
+        assert_eq!(read, vec![
+            (TraceDirection::Request, 0, b"ping".as_slice()),
+            (TraceDirection::Response, 16, [0, 1, 2, 255].as_slice()),
+            (TraceDirection::Request, 32, b"".as_slice()),
+        ]);
+        assert!(records.iter().all(|r| r.wall_clock_ms >= before && r.wall_clock_ms <= unix_millis()));
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn truncated_last_record_is_dropped() {
+        let dir = temp_dir("truncated");
+        let path = dir.join("session.sc2trace");
+        write_sample(&path);
+        let bytes = fs::read(&path).unwrap();
+        // Cut the last record (header only, empty payload) in half
+        fs::write(&path, &bytes[..bytes.len() - RECORD_HEADER_LEN / 2]).unwrap();
+
+        let records = read_trace(&path).unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1].payload, vec![0, 1, 2, 255]);
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn bad_magic_is_rejected() {
+        let dir = temp_dir("bad-magic");
+        let path = dir.join("session.sc2trace");
+        write_sample(&path);
+        let mut bytes = fs::read(&path).unwrap();
+        bytes[0] = b'X';
+        fs::write(&path, &bytes).unwrap();
+        assert_eq!(read_trace(&path).unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        fs::write(&path, b"AIUR").unwrap();
+        assert_eq!(read_trace(&path).unwrap_err().kind(), io::ErrorKind::InvalidData);
+        fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -4,12 +4,21 @@ use bevy_egui::{egui, EguiContexts};
 use crate::units::{SelectedUnit, UnitRegistry, UnitTag, UnitProto, get_set_fields, CurrentOrderAbility, UnitType};
 use crate::net_helpers::send_create_game_request;
 use sc2_proto::sc2api::{Request, LocalMap, PlayerSetup, PlayerType, Difficulty};
+use crate::ui::game_config_panel::parse_difficulty;
 use sc2_proto::common::Race;
 use protobuf::RepeatedField;
-use crate::app_settings::AppSettings;
-use crate::bot_runner::StartBotProcessesEvent;
+use crate::app_settings::{AppSettings, LAST_GAME_PRESET};
+use std::path::{Path, PathBuf};
+use crate::matches::MatchRecorder;
+use crate::bot_runner::{BotLaunch, BotProcessStatus, BotRole, StartBotProcessesEvent};
+use std::net::SocketAddr;
+use crate::controller::{PlayerPerspective, ProxyGameEndedEvent, ProxyListenAddr};
+use crate::series::SeriesRunner;
+use crate::ladder::LadderRunner;
+use crate::scenario::Scenario;
 
 pub(crate) mod game_config_panel;
+pub(crate) mod bot_console;
 mod setup_game_config_panel; // kept for now if referenced elsewhere
 pub(crate) use game_config_panel::{GameConfigPanel, GameType, show_game_config_panel};
 
@@ -20,6 +29,10 @@ pub enum AppState { StartScreen, GameScreen }
 #[derive(Resource, Default)]
 pub struct PendingCreateGameRequest(pub Option<Request>);
 
+/// Event sent when `pending_create_game_system` could not send the pending request
+#[derive(Event)]
+pub struct CreateGameFailedEvent(pub String);
+
 #[derive(Component)]
 pub struct MainCamera;
 
@@ -62,58 +75,98 @@ pub fn camera_controls(
     }
 }
 
+/// Build the event starting the configured bot processes, if any bot is set.
+/// `[[bots]]` entries are pointed at the address the proxy actually bound.
+pub fn bot_start_event(
+    panel: &GameConfigPanel,
+    settings: &AppSettings,
+    proxy_addr: Option<SocketAddr>,
+    log_dir: Option<PathBuf>,
+) -> Option<StartBotProcessesEvent> {
+    let (host, port) = match proxy_addr {
+        Some(addr) if addr.ip().is_unspecified() => ("127.0.0.1".to_string(), addr.port()),
+        Some(addr) => (addr.ip().to_string(), addr.port()),
+        None => (settings.starcraft.listen_url.clone(), settings.starcraft.listen_port),
+    };
+    let launch = |bot: &Option<String>, command: &String| match bot {
+        Some(name) => match panel.available_bots.iter().find(|b| &b.name == name) {
+            Some(definition) => Some(BotLaunch::from_definition(definition, &host, port)),
+            None => {
+                eprintln!("[ui] Bot '{}' is not defined in [[bots]]", name);
+                None
+            }
+        },
+        None if !command.is_empty() => Some(BotLaunch::Shell(command.clone())),
+        None => None,
+    };
+
+    let player_bot = launch(&panel.player_bot, &panel.bot_command);
+    let opponent_bot = if panel.game_type == GameType::VsBot {
+        launch(&panel.opponent_bot, &panel.bot_opponent_command)
+    } else {
+        None
+    };
+
+    if player_bot.is_none() && opponent_bot.is_none() {
+        return None;
+    }
+    Some(StartBotProcessesEvent { player_bot, opponent_bot, log_dir })
+}
+
+/// System to send a pending CreateGame request (from the CLI or an automatic restart).
+/// Runs without egui so it also drives headless mode.
+pub fn pending_create_game_system(
+    mut app_state: ResMut<AppState>,
+    game_config_panel: Res<GameConfigPanel>,
+    mut game_created: ResMut<GameCreated>,
+    mut pending_request: ResMut<PendingCreateGameRequest>,
+    app_settings: Res<AppSettings>,
+    mut bot_events: EventWriter<StartBotProcessesEvent>,
+    proxy_addr: Res<ProxyListenAddr>,
+    mut matches: ResMut<MatchRecorder>,
+    mut failed: EventWriter<CreateGameFailedEvent>,
+) {
+    let Some(req) = pending_request.0.take() else { return; };
+    let ws_url = format!("{}:{}/sc2api", app_settings.starcraft.upstream_url, app_settings.starcraft.upstream_port);
+
+    println!("[pending_create_game_system] Sending pending create game request");
+    let sent = req.clone();
+    match send_create_game_request(req, &ws_url, 5, 1) {
+        Err(e) => {
+            eprintln!("[pending_create_game_system] Failed to send create game request: {}", e);
+            failed.write(CreateGameFailedEvent(e));
+        },
+        Ok(_) => {
+            println!("[pending_create_game_system] Create game request sent successfully");
+            game_created.0 = true;
+            *app_state = AppState::GameScreen;
+            matches.begin(&app_settings, &game_config_panel, &sent);
+
+            // Send event to start bot processes
+            if let Some(event) = bot_start_event(&game_config_panel, &app_settings, proxy_addr.0, matches.current.clone()) {
+                bot_events.send(event);
+            }
+        }
+    }
+}
+
 pub fn ui_system(
     mut contexts: EguiContexts,
     mut app_state: ResMut<AppState>,
     mut game_config_panel: ResMut<GameConfigPanel>,
     mut game_created: ResMut<GameCreated>,
-    mut pending_request: ResMut<PendingCreateGameRequest>,
     selected: Res<SelectedUnit>,
     registry: Res<UnitRegistry>,
     unit_query: Query<(&UnitProto, &UnitTag, &CurrentOrderAbility, &UnitType)>,
     app_settings: Res<AppSettings>,
     mut bot_events: EventWriter<StartBotProcessesEvent>,
+    mut perspective: ResMut<PlayerPerspective>,
+    proxy_addr: Res<ProxyListenAddr>,
+    mut matches: ResMut<MatchRecorder>,
 ) {
     let Ok(ctx) = contexts.ctx_mut() else { return; };
     let ws_url = format!("{}:{}/sc2api", app_settings.starcraft.upstream_url, app_settings.starcraft.upstream_port);
     
-    // Check if there's a pending request from CLI to send
-    if let Some(req) = pending_request.0.take() {
-        println!("[ui_system] Sending pending create game request from CLI");
-        let res = send_create_game_request(req, &ws_url, 5, 1);
-        match res {
-            Err(e) => {
-                eprintln!("[ui_system] Failed to send create game request: {}", e);
-            },
-            Ok(_) => {
-                println!("[ui_system] Create game request sent successfully");
-                game_created.0 = true;
-                *app_state = AppState::GameScreen;
-
-                // Send event to start bot processes
-                let player_bot = if !game_config_panel.bot_command.is_empty() {
-                    Some(game_config_panel.bot_command.clone())
-                } else {
-                    None
-                };
-                let opponent_bot = if !game_config_panel.bot_opponent_command.is_empty()
-                    && game_config_panel.game_type == GameType::VsBot {
-                    Some(game_config_panel.bot_opponent_command.clone())
-                } else {
-                    None
-                };
-
-                if player_bot.is_some() || opponent_bot.is_some() {
-                    bot_events.send(StartBotProcessesEvent {
-                        player_bot_command: player_bot,
-                        opponent_bot_command: opponent_bot,
-                    });
-                }
-            }
-        }
-        return;
-    }
-    
     match *app_state {
         AppState::StartScreen => {
             egui::CentralPanel::default().show(ctx, |ui| {
@@ -123,33 +176,24 @@ pub fn ui_system(
                     let res = build_create_game_request(&game_config_panel)
                         .and_then(|req| {
                             println!("Sending create game request: {:?}", req);
-                            send_create_game_request(req, &ws_url, 5, 1)
+                            send_create_game_request(req.clone(), &ws_url, 5, 1).map(|_| req)
                         });
                     match res {
                         Err(e) => { ui.label(e); },
-                        Ok(_) => {
+                        Ok(sent) => {
                             game_created.0 = true;
                             *app_state = AppState::GameScreen;
                             ui.label("Create game request sent successfully.");
+                            matches.begin(&app_settings, &game_config_panel, &sent);
 
-                            // Send event to start bot processes
-                            let player_bot = if !game_config_panel.bot_command.is_empty() {
-                                Some(game_config_panel.bot_command.clone())
-                            } else {
-                                None
-                            };
-                            let opponent_bot = if !game_config_panel.bot_opponent_command.is_empty()
-                                && game_config_panel.game_type == GameType::VsBot {
-                                Some(game_config_panel.bot_opponent_command.clone())
-                            } else {
-                                None
-                            };
+                            // Remember these choices for the next run
+                            if let Err(e) = game_config_panel.to_defaults().save_preset(Path::new(LAST_GAME_PRESET)) {
+                                eprintln!("[ui_system] {}", e);
+                            }
 
-                            if player_bot.is_some() || opponent_bot.is_some() {
-                                bot_events.send(StartBotProcessesEvent {
-                                    player_bot_command: player_bot,
-                                    opponent_bot_command: opponent_bot,
-                                });
+                            // Send event to start bot processes
+                            if let Some(event) = bot_start_event(&game_config_panel, &app_settings, proxy_addr.0, matches.current.clone()) {
+                                bot_events.send(event);
                             }
                         }
                     }
@@ -158,6 +202,28 @@ pub fn ui_system(
         }
         AppState::GameScreen =>
         {
+            if perspective.known_players.len() > 1 {
+                egui::TopBottomPanel::top("perspective_panel").show(ctx, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label("Perspective:");
+                        let players: Vec<u32> = perspective.known_players.iter().copied().collect();
+                        for player_id in players {
+                            let selected = !perspective.merged && perspective.active == Some(player_id);
+                            if ui.selectable_label(selected, format!("Player {}", player_id)).clicked() {
+                                perspective.active = Some(player_id);
+                                perspective.merged = false;
+                            }
+                        }
+                        if ui.selectable_label(perspective.merged, "Both").clicked() {
+                            perspective.merged = true;
+                        }
+                        if perspective.merged {
+                            ui.label(format!("(colors as Player {})", perspective.active.unwrap_or(1)));
+                        }
+                    });
+                });
+            }
+
             egui::SidePanel::right("unit_info_panel")
                 .resizable(true)
                 .default_width(300.0)
@@ -199,10 +265,12 @@ pub enum DockerStatus {
     NotFound,
     Starting,
     Running,
+    /// Docker is not used (offline playback)
+    Disabled,
     Error(String),
 }
 
-pub fn status_bar_system(mut contexts: EguiContexts, docker_status: Res<DockerStatus>) {
+pub fn status_bar_system(mut contexts: EguiContexts, docker_status: Res<DockerStatus>, bot_status: Res<BotProcessStatus>) {
     let ctx = match contexts.ctx_mut() {
         Ok(ctx) => ctx,
         Err(_) => return,
@@ -216,8 +284,23 @@ pub fn status_bar_system(mut contexts: EguiContexts, docker_status: Res<DockerSt
                 DockerStatus::Running => ui.colored_label(egui::Color32::GREEN, "Running"),
                 DockerStatus::Starting => ui.colored_label(egui::Color32::YELLOW, "Starting"),
                 DockerStatus::NotFound => ui.colored_label(egui::Color32::RED, "Not Found"),
+                DockerStatus::Disabled => ui.colored_label(egui::Color32::GRAY, "Disabled (replay)"),
                 DockerStatus::Error(e) => ui.colored_label(egui::Color32::RED, format!("Error: {}", e)),
             };
+            for role in [BotRole::Player, BotRole::Opponent] {
+                let process = bot_status.get(role);
+                if process.command.is_none() {
+                    continue;
+                }
+                ui.separator();
+                ui.label(format!("{} bot:", role.label()));
+                match (&process.exit, process.running) {
+                    (_, true) => ui.colored_label(egui::Color32::GREEN, "Running"),
+                    (Some(exit), false) if process.error.is_none() => ui.colored_label(egui::Color32::GRAY, exit),
+                    (Some(exit), false) => ui.colored_label(egui::Color32::RED, exit),
+                    (None, false) => ui.colored_label(egui::Color32::RED, "Failed to start"),
+                };
+            }
         });
     });
 }
@@ -225,8 +308,53 @@ pub fn status_bar_system(mut contexts: EguiContexts, docker_status: Res<DockerSt
 #[derive(Resource, Default, Debug, PartialEq, Eq, Clone)]
 pub struct GameCreated(pub bool);
 
+/// System to go back to the start screen, or queue the next game, once the proxy reports a game ended
+pub fn game_end_system(
+    mut events: EventReader<ProxyGameEndedEvent>,
+    mut app_state: ResMut<AppState>,
+    mut game_created: ResMut<GameCreated>,
+    mut pending_request: ResMut<PendingCreateGameRequest>,
+    game_config_panel: Res<GameConfigPanel>,
+    series: Option<Res<SeriesRunner>>,
+    ladder: Option<Res<LadderRunner>>,
+) {
+    if events.read().last().is_none() {
+        return;
+    }
+    game_created.0 = false;
+
+    // A running series or ladder queues its next game itself
+    if series.is_some_and(|s| !s.is_finished()) || ladder.is_some_and(|l| !l.is_finished()) {
+        return;
+    }
+
+    if !game_config_panel.auto_restart {
+        println!("[game_end_system] Game finished, returning to start screen");
+        *app_state = AppState::StartScreen;
+        return;
+    }
+
+    // pending_create_game_system sends the request and restarts the bots, like a CLI-created game
+    match build_create_game_request(&game_config_panel) {
+        Ok(req) => {
+            println!("[game_end_system] Game finished, creating the next game");
+            pending_request.0 = Some(req);
+        }
+        Err(e) => {
+            eprintln!("[game_end_system] Failed to build next game request: {}", e);
+            *app_state = AppState::StartScreen;
+        }
+    }
+}
+
 pub fn build_create_game_request(panel: &GameConfigPanel) -> Result<Request, String> {
-    let (Some(map_name), Some(game_type)) = (panel.map_name.clone(), Some(panel.game_type.clone())) else {
+    // The scenario is checked now so a bad file fails before the game starts; its map wins over the panel's
+    let scenario = match panel.scenario.as_deref() {
+        Some(path) => Some(Scenario::load(Path::new(path))?),
+        None => None,
+    };
+    let map_name = scenario.and_then(|s| s.map).or_else(|| panel.map_name.clone());
+    let (Some(map_name), Some(game_type)) = (map_name, Some(panel.game_type.clone())) else {
         return Err("Please select a map and fill all required fields.".to_string());
     };
     let mut req = Request::new();
@@ -237,7 +365,7 @@ pub fn build_create_game_request(panel: &GameConfigPanel) -> Result<Request, Str
 
     let mut participant_setup = PlayerSetup::default();
     participant_setup.set_field_type(PlayerType::Participant);
-    participant_setup.set_race(Race::Random);
+    participant_setup.set_race(panel.player_race);
     participant_setup.set_player_name(panel.player_name.clone());
 
     let mut opponent_setup = PlayerSetup::default();
@@ -245,17 +373,16 @@ pub fn build_create_game_request(panel: &GameConfigPanel) -> Result<Request, Str
         GameType::VsAI => {
             opponent_setup.set_field_type(PlayerType::Computer);
             opponent_setup.set_race(panel.ai_race.unwrap_or(Race::Random));
-            opponent_setup.set_difficulty(match panel.ai_difficulty.as_deref() {
-                Some("Easy") => Difficulty::Easy,
-                Some("Medium") => Difficulty::Medium,
-                Some("Hard") => Difficulty::Hard,
-                Some("Cheat") => Difficulty::CheatInsane,
-                _ => Difficulty::Medium,
-            });
+            opponent_setup.set_difficulty(
+                panel.ai_difficulty.as_deref().and_then(parse_difficulty).unwrap_or(Difficulty::Medium)
+            );
+            if let Some(build) = panel.ai_build {
+                opponent_setup.set_ai_build(build);
+            }
         }
         GameType::VsBot => {
             opponent_setup.set_field_type(PlayerType::Participant);
-            opponent_setup.set_race(Race::Random);
+            opponent_setup.set_race(panel.ai_race.unwrap_or(Race::Random));
             opponent_setup.set_player_name(panel.bot_name.clone().unwrap_or_default());
         }
     }
@@ -0,0 +1,118 @@
+use bevy::prelude::*;
+use bevy_egui::{egui, EguiContexts};
+use crate::bot_runner::{BotControlAction, BotControlEvent, BotLogLine, BotProcessStatus, BotRole, BotStream};
+use crate::ui::AppState;
+
+/// State of the bot console panel shown under the map
+#[derive(Resource)]
+pub struct BotConsoleState {
+    pub open: bool,
+    pub active: BotRole,
+    pub filter: String,
+    pub stderr_only: bool,
+    pub auto_scroll: bool,
+}
+
+impl Default for BotConsoleState {
+    fn default() -> Self {
+        Self {
+            open: true,
+            active: BotRole::Player,
+            filter: String::new(),
+            stderr_only: false,
+            auto_scroll: true,
+        }
+    }
+}
+
+fn matches_filter(line: &BotLogLine, filter: &str, stderr_only: bool) -> bool {
+    if stderr_only && line.stream != BotStream::Stderr {
+        return false;
+    }
+    filter.is_empty() || line.text.to_lowercase().contains(filter)
+}
+
+/// Bottom panel with one tab per bot streaming its stdout and stderr
+pub fn bot_console_system(
+    mut contexts: EguiContexts,
+    mut state: ResMut<BotConsoleState>,
+    bot_status: Res<BotProcessStatus>,
+    app_state: Res<AppState>,
+    mut control: EventWriter<BotControlEvent>,
+) {
+    if *app_state != AppState::GameScreen {
+        return;
+    }
+    let Ok(ctx) = contexts.ctx_mut() else { return; };
+
+    egui::TopBottomPanel::bottom("bot_console")
+        .resizable(state.open)
+        .default_height(200.0)
+        .show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                let toggle = if state.open { "▼ Bot console" } else { "▶ Bot console" };
+                if ui.button(toggle).clicked() {
+                    state.open = !state.open;
+                }
+                for role in [BotRole::Player, BotRole::Opponent] {
+                    let mut label = format!("{} ({})", role.label(), bot_status.output(role).len());
+                    if bot_status.running(role) {
+                        label.push_str(" ●");
+                    }
+                    if ui.selectable_label(state.active == role, label).clicked() {
+                        state.active = role;
+                        state.open = true;
+                    }
+                }
+                ui.separator();
+                let role = state.active;
+                let process = bot_status.get(role);
+                if ui.add_enabled(process.running, egui::Button::new("Stop")).clicked() {
+                    control.write(BotControlEvent { role, action: BotControlAction::Stop });
+                }
+                if ui.add_enabled(process.command.is_some(), egui::Button::new("Restart")).clicked() {
+                    control.write(BotControlEvent { role, action: BotControlAction::Restart });
+                }
+            });
+            if !state.open {
+                return;
+            }
+
+            let role = state.active;
+            if let Some(error) = bot_status.error(role) {
+                ui.colored_label(egui::Color32::RED, error);
+            }
+
+            let filter = state.filter.to_lowercase();
+            let lines: Vec<&BotLogLine> = bot_status.output(role).iter()
+                .filter(|line| matches_filter(line, &filter, state.stderr_only))
+                .collect();
+
+            ui.horizontal(|ui| {
+                ui.label("Filter:");
+                ui.text_edit_singleline(&mut state.filter);
+                ui.checkbox(&mut state.stderr_only, "stderr only");
+                ui.checkbox(&mut state.auto_scroll, "Auto-scroll");
+                if ui.button("Copy").clicked() {
+                    let text = lines.iter().map(|line| line.text.as_str()).collect::<Vec<_>>().join("\n");
+                    ui.ctx().copy_text(text);
+                }
+                ui.label(format!("{} lines", lines.len()));
+            });
+            ui.separator();
+
+            let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+            egui::ScrollArea::vertical()
+                .auto_shrink([false, false])
+                .stick_to_bottom(state.auto_scroll)
+                .show_rows(ui, row_height, lines.len(), |ui, rows| {
+                    for line in &lines[rows] {
+                        let text = egui::RichText::new(&line.text).monospace();
+                        match line.stream {
+                            BotStream::Stdout => ui.label(text),
+                            BotStream::Stderr => ui.label(text.color(egui::Color32::from_rgb(255, 110, 110))),
+                        };
+                    }
+                });
+        });
+}
@@ -3,7 +3,8 @@ use std::path::Path;
 use bevy::prelude::*;
 use bevy_egui::egui;
 use sc2_proto::common::Race;
-use crate::app_settings::GameConfigPanelDefaults;
+use sc2_proto::sc2api::{AIBuild, Difficulty};
+use crate::app_settings::{BotDefinition, GameConfigPanelDefaults};
 
 #[derive(Resource, Default)]
 pub struct GameConfigPanel {
@@ -11,14 +12,29 @@ pub struct GameConfigPanel {
     pub map_name: Option<String>,
     pub available_maps: Vec<String>,
     pub player_name: String,
+    pub player_race: Race,
     pub ai_difficulty: Option<String>,
+    /// Race of the opponent, computer or bot
     pub ai_race: Option<Race>,
+    /// Build of a computer opponent; `None` lets the AI pick
+    pub ai_build: Option<AIBuild>,
     pub bot_name: Option<String>,
     pub disable_fog: bool,
     pub random_seed: Option<u32>,
     pub realtime: bool,
     pub bot_command: String,
     pub bot_opponent_command: String,
+    /// `[[bots]]` entry launched as the player bot; overrides `bot_command` when set
+    pub player_bot: Option<String>,
+    /// `[[bots]]` entry launched as the opponent bot; overrides `bot_opponent_command` when set
+    pub opponent_bot: Option<String>,
+    /// `[[bots]]` entries offered in the dropdowns
+    pub available_bots: Vec<BotDefinition>,
+    /// Create the next game automatically when the current one ends
+    pub auto_restart: bool,
+    /// Scenario file set up with debug commands when the game starts.
+    /// Given per run (panel or `--scenario`), never saved, since it replaces the starting units.
+    pub scenario: Option<String>,
 }
 
 impl GameConfigPanel {
@@ -26,6 +42,7 @@ impl GameConfigPanel {
         let available_maps = list_maps_folder();
         Self {
             available_maps,
+            player_race: Race::Random,
             ai_race: Some(Race::Random),
             ..Default::default()
         }
@@ -38,34 +55,137 @@ impl GameConfigPanel {
         };
         let map_name = defaults.map_name.clone().or_else(|| available_maps.get(0).cloned());
         let player_name = defaults.player_name.clone().unwrap_or_else(|| "Player1".to_string());
+        let player_race = defaults.player_race.as_deref().and_then(parse_race).unwrap_or(Race::Random);
         let ai_difficulty = defaults.ai_difficulty.clone();
-        let ai_race = match defaults.ai_race.as_deref() {
-            Some("Terran") => Some(Race::Terran),
-            Some("Protoss") => Some(Race::Protoss),
-            Some("Zerg") => Some(Race::Zerg),
-            Some("Random") | _ => Some(Race::Random),
-        };
+        let ai_race = Some(defaults.ai_race.as_deref().and_then(parse_race).unwrap_or(Race::Random));
+        let ai_build = defaults.ai_build.as_deref().and_then(parse_ai_build);
         let bot_name = defaults.bot_name.clone();
         let disable_fog = defaults.disable_fog.unwrap_or(false);
         let random_seed = defaults.random_seed;
         let realtime = defaults.realtime.unwrap_or(false);
         let bot_command = defaults.bot_command.clone().unwrap_or_default();
         let bot_opponent_command = defaults.bot_opponent_command.clone().unwrap_or_default();
+        let player_bot = defaults.player_bot.clone();
+        let opponent_bot = defaults.opponent_bot.clone();
+        let auto_restart = defaults.auto_restart.unwrap_or(false);
         Self {
             game_type,
             map_name,
             available_maps,
             player_name,
+            player_race,
             ai_difficulty,
             ai_race,
+            ai_build,
             bot_name,
             disable_fog,
             random_seed,
             realtime,
             bot_command,
             bot_opponent_command,
+            player_bot,
+            opponent_bot,
+            available_bots: Vec::new(),
+            auto_restart,
+            scenario: None,
         }
     }
+
+    /// Current choices in the form they are stored in `[game_config_panel]`
+    pub fn to_defaults(&self) -> GameConfigPanelDefaults {
+        GameConfigPanelDefaults {
+            game_type: Some(format!("{:?}", self.game_type)),
+            map_name: self.map_name.clone(),
+            player_name: Some(self.player_name.clone()),
+            player_race: Some(format!("{:?}", self.player_race)),
+            ai_difficulty: self.ai_difficulty.clone(),
+            ai_race: self.ai_race.map(|r| format!("{:?}", r)),
+            ai_build: self.ai_build.map(|b| ai_build_label(b).to_string()),
+            bot_name: self.bot_name.clone(),
+            disable_fog: Some(self.disable_fog),
+            random_seed: self.random_seed,
+            realtime: Some(self.realtime),
+            bot_command: Some(self.bot_command.clone()),
+            bot_opponent_command: Some(self.bot_opponent_command.clone()),
+            player_bot: self.player_bot.clone(),
+            opponent_bot: self.opponent_bot.clone(),
+            auto_restart: Some(self.auto_restart),
+        }
+    }
+}
+
+const RACES: [Race; 4] = [Race::Random, Race::Terran, Race::Zerg, Race::Protoss];
+
+const DIFFICULTIES: [Difficulty; 10] = [
+    Difficulty::VeryEasy,
+    Difficulty::Easy,
+    Difficulty::Medium,
+    Difficulty::MediumHard,
+    Difficulty::Hard,
+    Difficulty::Harder,
+    Difficulty::VeryHard,
+    Difficulty::CheatVision,
+    Difficulty::CheatMoney,
+    Difficulty::CheatInsane,
+];
+
+const AI_BUILDS: [AIBuild; 6] = [
+    AIBuild::RandomBuild,
+    AIBuild::Rush,
+    AIBuild::Timing,
+    AIBuild::Power,
+    AIBuild::Macro,
+    AIBuild::Air,
+];
+
+fn ai_build_label(build: AIBuild) -> &'static str {
+    match build {
+        AIBuild::RandomBuild => "Random",
+        AIBuild::Rush => "Rush",
+        AIBuild::Timing => "Timing",
+        AIBuild::Power => "Power",
+        AIBuild::Macro => "Macro",
+        AIBuild::Air => "Air",
+    }
+}
+
+pub fn parse_race(race: &str) -> Option<Race> {
+    match race.to_lowercase().as_str() {
+        "terran" => Some(Race::Terran),
+        "zerg" => Some(Race::Zerg),
+        "protoss" => Some(Race::Protoss),
+        "random" => Some(Race::Random),
+        _ => None,
+    }
+}
+
+/// Parse a difficulty name as spelled in `Difficulty` (case-insensitive); "Cheat" is kept as an alias
+pub fn parse_difficulty(difficulty: &str) -> Option<Difficulty> {
+    match difficulty.to_lowercase().as_str() {
+        "veryeasy" => Some(Difficulty::VeryEasy),
+        "easy" => Some(Difficulty::Easy),
+        "medium" => Some(Difficulty::Medium),
+        "mediumhard" => Some(Difficulty::MediumHard),
+        "hard" => Some(Difficulty::Hard),
+        "harder" => Some(Difficulty::Harder),
+        "veryhard" => Some(Difficulty::VeryHard),
+        "cheatvision" => Some(Difficulty::CheatVision),
+        "cheatmoney" => Some(Difficulty::CheatMoney),
+        "cheatinsane" | "cheat" => Some(Difficulty::CheatInsane),
+        _ => None,
+    }
+}
+
+pub fn parse_ai_build(build: &str) -> Option<AIBuild> {
+    match build.to_lowercase().as_str() {
+        "random" | "randombuild" => Some(AIBuild::RandomBuild),
+        "rush" => Some(AIBuild::Rush),
+        "timing" => Some(AIBuild::Timing),
+        "power" => Some(AIBuild::Power),
+        "macro" => Some(AIBuild::Macro),
+        "air" => Some(AIBuild::Air),
+        _ => None,
+    }
 }
 
 pub fn list_maps_folder() -> Vec<String> {
@@ -125,23 +245,43 @@ pub fn show_game_config_panel(ui: &mut egui::Ui, panel: &mut GameConfigPanel) ->
     ui.label("Player Name:");
     ui.text_edit_singleline(&mut panel.player_name);
 
+    ui.label("Player Race:");
+    egui::ComboBox::from_id_source("player_race_combo")
+        .selected_text(format!("{:?}", panel.player_race))
+        .show_ui(ui, |ui| {
+            for race in RACES {
+                ui.selectable_value(&mut panel.player_race, race, format!("{:?}", race));
+            }
+        });
+
+    ui.label("Opponent Race:");
+    egui::ComboBox::from_id_source("ai_race_combo")
+        .selected_text(panel.ai_race.map(|r| format!("{:?}", r)).unwrap_or_else(|| "Select race".to_string()))
+        .show_ui(ui, |ui| {
+            for race in RACES {
+                ui.selectable_value(&mut panel.ai_race, Some(race), format!("{:?}", race));
+            }
+        });
+
     match panel.game_type {
         GameType::VsAI => {
             ui.label("AI Difficulty:");
             egui::ComboBox::from_id_source("ai_difficulty_combo")
                 .selected_text(panel.ai_difficulty.clone().unwrap_or_else(|| "Select difficulty".to_string()))
                 .show_ui(ui, |ui| {
-                    for diff in ["Easy", "Medium", "Hard", "Cheat"] {
-                        ui.selectable_value(&mut panel.ai_difficulty, Some(diff.to_string()), diff);
+                    for diff in DIFFICULTIES {
+                        let name = format!("{:?}", diff);
+                        ui.selectable_value(&mut panel.ai_difficulty, Some(name.clone()), name);
                     }
                 });
 
-            ui.label("AI Race:");
-            egui::ComboBox::from_id_source("ai_race_combo")
-                .selected_text(panel.ai_race.map(|r| format!("{:?}", r)).unwrap_or_else(|| "Select race".to_string()))
+            ui.label("AI Build:");
+            egui::ComboBox::from_id_source("ai_build_combo")
+                .selected_text(panel.ai_build.map(ai_build_label).unwrap_or("Any"))
                 .show_ui(ui, |ui| {
-                    for &race in &[Race::Random, Race::Terran, Race::Zerg, Race::Protoss] {
-                        ui.selectable_value(&mut panel.ai_race, Some(race), format!("{:?}", race));
+                    ui.selectable_value(&mut panel.ai_build, None, "Any");
+                    for build in AI_BUILDS {
+                        ui.selectable_value(&mut panel.ai_build, Some(build), ai_build_label(build));
                     }
                 });
         }
@@ -149,27 +289,53 @@ pub fn show_game_config_panel(ui: &mut egui::Ui, panel: &mut GameConfigPanel) ->
             ui.label("Bot Name:");
             ui.text_edit_singleline(panel.bot_name.get_or_insert_with(String::new));
             
-            ui.label("Bot Opponent Command:");
-            ui.text_edit_singleline(&mut panel.bot_opponent_command);
-            ui.label("(Bash command to run opponent bot)");
+            if !panel.available_bots.is_empty() {
+                ui.label("Opponent Bot:");
+                if let Some(bot) = bot_combo(ui, "opponent_bot_combo", &panel.available_bots, &mut panel.opponent_bot) {
+                    panel.bot_name = Some(bot.name.clone());
+                    panel.ai_race = bot.race.as_deref().and_then(parse_race).or(panel.ai_race);
+                }
+            }
+            if panel.opponent_bot.is_none() {
+                ui.label("Bot Opponent Command:");
+                ui.text_edit_singleline(&mut panel.bot_opponent_command);
+                ui.label("(Bash command to run opponent bot)");
+            }
         }
     }
 
     ui.add_space(10.0);
-    ui.label("Bot Command:");
-    ui.text_edit_singleline(&mut panel.bot_command);
-    ui.label("(Optional: Bash command to run player bot)");
+    if !panel.available_bots.is_empty() {
+        ui.label("Player Bot:");
+        if let Some(bot) = bot_combo(ui, "player_bot_combo", &panel.available_bots, &mut panel.player_bot) {
+            panel.player_race = bot.race.as_deref().and_then(parse_race).unwrap_or(panel.player_race);
+        }
+    }
+    if panel.player_bot.is_none() {
+        ui.label("Bot Command:");
+        ui.text_edit_singleline(&mut panel.bot_command);
+        ui.label("(Optional: Bash command to run player bot)");
+    }
     
     ui.add_space(10.0);
     ui.horizontal(|ui| {
         ui.checkbox(&mut panel.disable_fog, "Disable Fog");
         ui.checkbox(&mut panel.realtime, "Realtime");
+        ui.checkbox(&mut panel.auto_restart, "Auto-create next game");
         ui.label("Random Seed:");
         let mut seed_str = panel.random_seed.map(|v| v.to_string()).unwrap_or_default();
         if ui.text_edit_singleline(&mut seed_str).changed() {
             panel.random_seed = seed_str.parse().ok();
         }
     });
+    ui.horizontal(|ui| {
+        ui.label("Scenario:");
+        let mut scenario = panel.scenario.clone().unwrap_or_default();
+        if ui.text_edit_singleline(&mut scenario).changed() {
+            panel.scenario = Some(scenario).filter(|s| !s.is_empty());
+        }
+        ui.label("(Optional: .toml/.json file with units placed at start)");
+    });
     ui.add_space(10.0);
     if ui.button("Create Game").clicked() {
         start_game = true;
@@ -177,6 +343,23 @@ pub fn show_game_config_panel(ui: &mut egui::Ui, panel: &mut GameConfigPanel) ->
     start_game
 }
 
+/// Dropdown of the `[[bots]]` entries; "Custom command" clears the choice.
+/// Returns the newly picked bot, if the selection changed to one.
+fn bot_combo(ui: &mut egui::Ui, id: &str, bots: &[BotDefinition], selected: &mut Option<String>) -> Option<BotDefinition> {
+    let mut picked = None;
+    egui::ComboBox::from_id_salt(id)
+        .selected_text(selected.clone().unwrap_or_else(|| "Custom command".to_string()))
+        .show_ui(ui, |ui| {
+            ui.selectable_value(selected, None, "Custom command");
+            for bot in bots {
+                if ui.selectable_value(selected, Some(bot.name.clone()), &bot.name).clicked() {
+                    picked = Some(bot.clone());
+                }
+            }
+        });
+    picked
+}
+
 #[derive(PartialEq, Eq, Clone, Debug, Default)]
 pub enum GameType {
     #[default]
@@ -3,7 +3,7 @@ use std::collections::{HashMap, HashSet};
 use bevy::sprite::Anchor;
 use sc2_proto::sc2api::ResponseObservation;
 use protobuf::reflect::ReflectFieldRef;
-use protobuf::Message;
+use protobuf::{Message, ProtobufEnum};
 use crate::entity_system::EntitySystem;
 use bevy_health_bar3d::prelude::*;
 use sc2_proto::raw::Alliance;
@@ -124,7 +124,7 @@ pub fn handle_observation(
     registry: &mut ResMut<UnitRegistry>,
     entity_system: &Res<EntitySystem>,
     obs_msg: &ResponseObservation,
-    unit_query: Query<&UnitBuildProgress>,
+    unit_query: &Query<&UnitBuildProgress>,
     seen_tags: &mut ResMut<ObservationUnitTags>,
     map_size: (f32, f32),
 ) {
@@ -146,18 +146,14 @@ pub fn handle_observation(
         let build_progress = unit.build_progress.unwrap_or(0.0);
         let unit_type = unit.unit_type.unwrap();
         let tile_size = entity_system.map_config.tile_size;
-        let world_x = x * tile_size - map_size.0 * tile_size / 2.0;
-        let world_y = y * tile_size - map_size.1 * tile_size / 2.0;
+        let world = world_position(x, y, map_size, tile_size);
 
         let unit_radius = unit.radius.unwrap_or(1.0);
 
         let first_order_ability = unit.orders.get(0).and_then(|o| o.ability_id);
 
-        //Apply reddish tint for enemy units
-        let sprite_color = match unit.alliance.as_ref().unwrap() {
-            Alliance::Enemy => Color::srgb(1.0, 0.5, 0.5),
-            _ =>  Color::WHITE
-        };
+        let alliance = unit.alliance.unwrap_or(Alliance::Neutral).value();
+        let sprite_color = alliance_color(alliance);
 
         // Get display info from an entity system
         // Use custom tile size if specified in config, otherwise use unit radius
@@ -170,11 +166,12 @@ pub fn handle_observation(
 
         if let Some(&entity) = registry.map.get(&tag) {
             commands.entity(entity).insert((
-                Transform::from_xyz(world_x, world_y, 1.0),
+                Transform::from_translation(world.extend(1.0)),
                 UnitHealth { current: health, max: max_health },
                 UnitShield { current: shield, max: max_shield },
                 UnitProto(unit.clone()),
                 CurrentOrderAbility(first_order_ability),
+                UnitAlliance(alliance),
             ));
 
             // Prevent flickering: only insert/remove build progress bar if needed
@@ -196,7 +193,7 @@ pub fn handle_observation(
                     anchor: Anchor::Center,
                     ..default()
                 },
-                Transform::from_xyz(world_x, world_y, 1.0),
+                Transform::from_translation(world.extend(1.0)),
                 UnitTag(tag),
                 UnitType(unit_type),
                 UnitHealth { current: health, max: max_health },
@@ -208,6 +205,7 @@ pub fn handle_observation(
                 },
                 UnitProto(unit.clone()),
                 CurrentOrderAbility(first_order_ability),
+                UnitAlliance(alliance),
             ));
 
             // Conditionally add shield bar
@@ -242,6 +240,89 @@ pub fn handle_observation(
     }
 }
 
+/// Convert SC2 map coordinates to world coordinates, with the map centered on the origin
+pub fn world_position(x: f32, y: f32, map_size: (f32, f32), tile_size: f32) -> Vec2 {
+    Vec2::new(
+        x * tile_size - map_size.0 * tile_size / 2.0,
+        y * tile_size - map_size.1 * tile_size / 2.0,
+    )
+}
+
+/// Inverse of `world_position`
+pub fn map_position(world: Vec2, map_size: (f32, f32), tile_size: f32) -> Vec2 {
+    Vec2::new(
+        (world.x + map_size.0 * tile_size / 2.0) / tile_size,
+        (world.y + map_size.1 * tile_size / 2.0) / tile_size,
+    )
+}
+
+/// Sprite tint for an alliance value (1=Self, 2=Ally, 3=Neutral, 4=Enemy)
+pub fn alliance_color(alliance: i32) -> Color {
+    match Alliance::from_i32(alliance) {
+        Some(Alliance::Enemy) => Color::srgb(1.0, 0.5, 0.5),
+        Some(Alliance::Ally) => Color::srgb(0.6, 1.0, 0.6),
+        Some(Alliance::Neutral) => Color::srgb(0.8, 0.8, 0.8),
+        _ => Color::WHITE,
+    }
+}
+
+/// Re-tint sprites whose alliance changed, e.g. after switching perspective
+pub fn update_unit_alliance_colors(mut query: Query<(&UnitAlliance, &mut Sprite), Changed<UnitAlliance>>) {
+    for (alliance, mut sprite) in query.iter_mut() {
+        sprite.color = alliance_color(alliance.0);
+    }
+}
+
+/// Alliance of another player's unit as seen from the viewpoint player (two-sided games)
+fn remap_alliance(alliance: Alliance) -> Alliance {
+    match alliance {
+        Alliance::value_Self => Alliance::Enemy,
+        Alliance::Enemy => Alliance::value_Self,
+        other => other,
+    }
+}
+
+/// Combine the latest observation of every player into one, as seen by `viewpoint`.
+/// Units from other players' streams get their alliance remapped; a unit seen by several
+/// players keeps its owner's copy, which has the most complete data.
+pub fn merge_observations(viewpoint: u32, observations: &HashMap<u32, ResponseObservation>) -> ResponseObservation {
+    // Without an observation of the viewpoint yet, start from the lowest player ID instead
+    let base = if observations.contains_key(&viewpoint) {
+        Some(viewpoint)
+    } else {
+        observations.keys().min().copied()
+    };
+    let mut merged = base
+        .and_then(|player| observations.get(&player))
+        .cloned()
+        .unwrap_or_default();
+
+    let raw = merged.mut_observation().mut_raw_data();
+    let mut index: HashMap<u64, usize> = raw.get_units()
+        .iter()
+        .enumerate()
+        .map(|(i, u)| (u.get_tag(), i))
+        .collect();
+
+    for (_, obs) in observations.iter().filter(|(player, _)| Some(**player) != base) {
+        for unit in obs.get_observation().get_raw_data().get_units() {
+            let owned = unit.get_alliance() == Alliance::value_Self;
+            let mut unit = unit.clone();
+            unit.set_alliance(remap_alliance(unit.get_alliance()));
+
+            match index.get(&unit.get_tag()) {
+                Some(&i) if owned => raw.mut_units()[i] = unit,
+                Some(_) => {}
+                None => {
+                    index.insert(unit.get_tag(), raw.get_units().len());
+                    raw.mut_units().push(unit);
+                }
+            }
+        }
+    }
+    merged
+}
+
 pub fn get_set_fields(unit: &sc2_proto::raw::Unit) -> Vec<(String, String)> {
     let descriptor = unit.descriptor();
     let mut result = Vec::new();